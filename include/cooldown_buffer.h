@@ -0,0 +1,50 @@
+/*
+ * C bindings of the cooldown-buffer crate, built with the `ffi` feature. See the `ffi` module
+ * documentation for the details of every function.
+ */
+#ifndef COOLDOWN_BUFFER_H
+#define COOLDOWN_BUFFER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque buffer handle, freed with cooldown_buffer_free. */
+typedef struct FfiBuffer FfiBuffer;
+
+/* Opaque batch handle, freed with cooldown_batch_free. */
+typedef struct FfiBatch FfiBatch;
+
+FfiBuffer *cooldown_buffer_new(uint64_t cooldown_ms);
+
+/* Returns 0 on success and -1 when the buffer is not running anymore. */
+int32_t cooldown_buffer_send(const FfiBuffer *buffer, const uint8_t *data, size_t len);
+
+/* Returns 0 on success and -1 when the buffer is not running anymore. */
+int32_t cooldown_buffer_flush(const FfiBuffer *buffer);
+
+/*
+ * Returns NULL when there is no batch, then status is 1 when no batch was emitted within
+ * timeout_ms and -1 when the buffer is not running anymore. It's 0 when the batch is returned.
+ * status can be NULL.
+ */
+FfiBatch *cooldown_buffer_recv(const FfiBuffer *buffer, uint64_t timeout_ms, int32_t *status);
+
+/* Pending items are discarded, flush and receive them first to keep them. */
+void cooldown_buffer_free(FfiBuffer *buffer);
+
+size_t cooldown_batch_len(const FfiBatch *batch);
+
+/* Returns NULL when there is no item at index, valid until the batch is freed. */
+const uint8_t *cooldown_batch_item(const FfiBatch *batch, size_t index, size_t *len);
+
+void cooldown_batch_free(FfiBatch *batch);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* COOLDOWN_BUFFER_H */
@@ -0,0 +1,136 @@
+use crate::buffer::Batcher;
+use std::cell::RefCell;
+use std::fmt::{self, Debug};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+type Acker = Arc<dyn Fn() + Send + Sync>;
+
+/// Batch emitted by [`cooldown_buffer_acked`](crate::cooldown_buffer_acked). The next batch is
+/// not emitted until this one is acknowledged with [`Acked::ack`], or all its copies are dropped.
+/// When the emission is split into chunks (see
+/// [`CooldownSender::set_chunk_size`](crate::CooldownSender::set_chunk_size)), each of them has to
+/// be acknowledged.
+/// It dereferences to the slice of emitted items.
+#[derive(Debug, Clone)]
+pub struct Acked<T> {
+    items: Vec<T>,
+    ack: Arc<Ack>,
+}
+
+impl<T> Acked<T> {
+    /// Lets the buffer emit the next batch. Acknowledging the batch again does nothing.
+    pub fn ack(&self) {
+        self.ack.ack();
+    }
+}
+
+impl<T> Deref for Acked<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.items
+    }
+}
+
+impl<T> AsRef<[T]> for Acked<T> {
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+
+/// Acknowledgement of one chunk of the emission.
+struct Ack {
+    acked: AtomicBool,
+    emission: Arc<Emission>,
+}
+
+impl Ack {
+    fn ack(&self) {
+        if !self.acked.swap(true, Ordering::Relaxed) {
+            self.emission.done();
+        }
+    }
+}
+
+impl Drop for Ack {
+    fn drop(&mut self) {
+        self.ack();
+    }
+}
+
+impl Debug for Ack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ack")
+            .field("acked", &self.acked)
+            .finish_non_exhaustive()
+    }
+}
+
+/// All the chunks emitted at once, acknowledged when each of them is. The batcher holds one
+/// share until the last chunk is created.
+struct Emission {
+    remaining: AtomicUsize,
+    acker: Option<Acker>,
+}
+
+impl Emission {
+    fn done(&self) {
+        if self.remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+            if let Some(acker) = &self.acker {
+                acker();
+            }
+        }
+    }
+}
+
+/// Creates [`Acked`] batches. The function telling the buffer about acknowledgements is set once
+/// the sender exists.
+#[derive(Default)]
+pub(crate) struct AckBatcher {
+    acker: Arc<OnceLock<Acker>>,
+    /// Emission the created chunks belong to.
+    emission: RefCell<Option<Arc<Emission>>>,
+}
+
+impl AckBatcher {
+    pub(crate) fn acker(&self) -> Arc<OnceLock<Acker>> {
+        self.acker.clone()
+    }
+}
+
+impl<T> Batcher<T, Acked<T>> for AckBatcher {
+    fn batch(&self, items: Vec<T>) -> Acked<T> {
+        let emission = self
+            .emission
+            .borrow_mut()
+            .get_or_insert_with(|| {
+                Arc::new(Emission {
+                    remaining: AtomicUsize::new(1),
+                    acker: self.acker.get().cloned(),
+                })
+            })
+            .clone();
+        emission.remaining.fetch_add(1, Ordering::AcqRel);
+        Acked {
+            items,
+            ack: Arc::new(Ack {
+                acked: AtomicBool::new(false),
+                emission,
+            }),
+        }
+    }
+
+    fn reset(&mut self) {
+        if let Some(emission) = self.emission.get_mut().take() {
+            emission.done();
+        }
+    }
+}
+
+impl Debug for AckBatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AckBatcher")
+    }
+}
@@ -0,0 +1,571 @@
+use crate::coalesce::Coalesce;
+use crate::error::Error;
+use crate::outbox::{OutboxReceiver, OutboxSender};
+use crate::shared::Shared;
+use crate::wal::Wal;
+use std::fmt::{self, Debug};
+use std::mem;
+use std::num::NonZeroUsize;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Items buffered by the buffer thread together with their copy in the log, so both are always
+/// changed at once. It's owned by the buffer thread, the handles only see what is published in
+/// [`Shared`].
+#[derive(Debug)]
+pub(crate) struct Buffer<T, B> {
+    items: Vec<T>,
+    /// When each of the items was received, in the same order as the items.
+    arrivals: Vec<Instant>,
+    wal: Option<Wal<T>>,
+    coalesce: Coalesce<T>,
+    batcher: IntoBatch<T, B>,
+    shared: Arc<Shared<B>>,
+    /// Receivers of items discarded without being emitted.
+    dead_letters: Vec<OutboxSender<Vec<T>>>,
+    /// Items of batches dropped by lagging receivers, they are discarded.
+    dropped: Vec<OutboxReceiver<Vec<T>>>,
+    /// Maximum number of items in the emitted batch, bigger batches are split.
+    chunk_size: Option<NonZeroUsize>,
+    /// Number of items emitted right away, also the limit for the chunk size.
+    capacity: Option<NonZeroUsize>,
+    weight: Option<Weight<T>>,
+    gate: Option<Gate>,
+    filter: Option<Box<dyn Filter<T>>>,
+    /// Number of leading items which were requeued, the filter can't suppress them.
+    requeued: usize,
+}
+
+/// Holds the emission back until the previous batch is acknowledged, see
+/// [`cooldown_buffer_acked`](crate::cooldown_buffer_acked).
+#[derive(Debug, Default)]
+struct Gate {
+    in_flight: bool,
+    /// Whether the emission was held back.
+    held: bool,
+}
+
+/// Turns buffered items into the batch.
+pub(crate) trait Batcher<T, B>: Debug + Send {
+    /// Called with every item pushed to the buffer.
+    fn observe(&mut self, _item: &T) {}
+
+    /// Creates the batch. It's called for every chunk of emitted items (see
+    /// [`Buffer::set_chunk_size`]), never with empty vector.
+    fn batch(&self, items: Vec<T>) -> B;
+
+    /// Called when all the pending items were emitted or dropped.
+    fn reset(&mut self) {}
+}
+
+/// Decides which of the items taken from the buffer are emitted.
+pub(crate) trait Filter<T>: Debug + Send {
+    /// Tells for each of `items` whether it's emitted.
+    fn keep(&mut self, items: &[T]) -> Vec<bool>;
+}
+
+/// Plain function needs no boxing, so items don't have to be `'static`.
+enum IntoBatch<T, B> {
+    Fn(fn(Vec<T>) -> B),
+    Batcher(Box<dyn Batcher<T, B>>),
+}
+
+impl<T, B> IntoBatch<T, B> {
+    fn observe(&mut self, item: &T) {
+        if let Self::Batcher(batcher) = self {
+            batcher.observe(item);
+        }
+    }
+
+    fn batch(&self, items: Vec<T>) -> B {
+        match self {
+            Self::Fn(into_batch) => into_batch(items),
+            Self::Batcher(batcher) => batcher.batch(items),
+        }
+    }
+
+    fn reset(&mut self) {
+        if let Self::Batcher(batcher) = self {
+            batcher.reset();
+        }
+    }
+}
+
+impl<T, B> Debug for IntoBatch<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fn(_) => write!(f, "Fn"),
+            Self::Batcher(batcher) => batcher.fmt(f),
+        }
+    }
+}
+
+/// Tells how much the item extends the cooldown, see
+/// [`cooldown_buffer_weighted`](crate::cooldown_buffer_weighted).
+struct Weight<T>(Box<dyn Fn(&T) -> u32 + Send>);
+
+impl<T> Debug for Weight<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Weight")
+    }
+}
+
+impl<T, B> Buffer<T, B> {
+    /// Creates empty buffer. Buffered items are turned into the batch with `into_batch`, which is
+    /// never called with empty vector.
+    pub(crate) fn new(into_batch: fn(Vec<T>) -> B) -> Self {
+        Self {
+            items: Vec::new(),
+            arrivals: Vec::new(),
+            wal: None,
+            coalesce: Coalesce::All,
+            batcher: IntoBatch::Fn(into_batch),
+            shared: Arc::new(Shared::new()),
+            dead_letters: Vec::new(),
+            dropped: Vec::new(),
+            chunk_size: None,
+            capacity: None,
+            weight: None,
+            gate: None,
+            filter: None,
+            requeued: 0,
+        }
+    }
+
+    /// Creates empty buffer turning buffered items into the batch with `batcher`.
+    pub(crate) fn with_batcher(batcher: impl Batcher<T, B> + 'static) -> Self {
+        Self {
+            batcher: IntoBatch::Batcher(Box::new(batcher)),
+            ..Self::new(|_| unreachable!("replaced with the batcher"))
+        }
+    }
+
+    /// Starts with items recovered from the log already buffered.
+    pub(crate) fn with_wal(mut self, mut wal: Wal<T>) -> Self {
+        self.items = wal.take_recovered();
+        self.items
+            .iter()
+            .for_each(|item| self.batcher.observe(item));
+        self.arrivals = vec![Instant::now(); self.items.len()];
+        self.wal = Some(wal);
+        self.shared.set_pending_len(self.items.len());
+        self
+    }
+
+    /// Changes what is kept when new item is pushed, see [`Coalesce`].
+    pub(crate) fn coalesce(mut self, coalesce: Coalesce<T>) -> Self {
+        self.coalesce = coalesce;
+        self
+    }
+
+    /// Makes the buffer emit items as soon as `capacity` of them is pending, never more at once.
+    /// Room for them is reserved again after every batch, so buffering never reallocates.
+    pub(crate) fn with_capacity(mut self, capacity: NonZeroUsize) -> Self {
+        self.items.reserve(capacity.get());
+        self.arrivals.reserve(capacity.get());
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Emits only the items kept by `filter`.
+    pub(crate) fn filtered(mut self, filter: impl Filter<T> + 'static) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Makes the buffer wait with the next emission until the previous batch is acknowledged.
+    pub(crate) fn gated(mut self) -> Self {
+        self.gate = Some(Gate::default());
+        self
+    }
+
+    /// Stops waiting for acknowledgements, e.g. before the last emission.
+    pub(crate) fn ungate(&mut self) {
+        self.gate = None;
+    }
+
+    /// Tells whether the pending items should be emitted without waiting for the cooldown.
+    pub(crate) fn is_full(&self) -> bool {
+        self.capacity
+            .is_some_and(|capacity| self.items.len() >= capacity.get())
+    }
+
+    /// Makes items extend the cooldown according to `weight`.
+    pub(crate) fn weighted<F>(mut self, weight: F) -> Self
+    where
+        F: 'static + Fn(&T) -> u32 + Send,
+    {
+        self.weight = Some(Weight(Box::new(weight)));
+        self
+    }
+
+    /// Returns the weight of the heaviest of `items`, `None` when items are not weighted.
+    pub(crate) fn weight<'a>(&self, items: impl IntoIterator<Item = &'a T>) -> Option<u32>
+    where
+        T: 'a,
+    {
+        let Weight(weight) = self.weight.as_ref()?;
+        Some(items.into_iter().map(weight).max().unwrap_or(0))
+    }
+
+    pub(crate) fn shared(&self) -> &Arc<Shared<B>> {
+        &self.shared
+    }
+
+    pub(crate) fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    pub(crate) fn push(&mut self, item: T) {
+        self.push_quietly(item);
+        self.shared.set_pending_len(self.items.len());
+    }
+
+    /// Puts previously emitted items back, before the pending ones, as they are older. Pending
+    /// items still replace requeued ones when items are coalesced.
+    pub(crate) fn requeue(&mut self, items: Vec<T>) {
+        let pending = take_keeping_room(&mut self.items, self.capacity);
+        let arrivals = take_keeping_room(&mut self.arrivals, self.capacity);
+        self.coalesce.reset();
+        items.into_iter().for_each(|item| self.push_quietly(item));
+        // Items requeued before are at the front of the pending ones.
+        self.requeued += self.items.len();
+        // Pending items are in the log already.
+        for (item, arrival) in pending.into_iter().zip(arrivals) {
+            self.insert(item, arrival);
+        }
+        self.shared.set_pending_len(self.items.len());
+    }
+
+    /// Removes `count` oldest items without emitting them.
+    pub(crate) fn drop_oldest(&mut self, count: usize) {
+        let count = count.min(self.items.len());
+        self.items.drain(..count);
+        self.arrivals.drain(..count);
+        self.requeued = self.requeued.saturating_sub(count);
+        self.shared.set_pending_len(self.items.len());
+    }
+
+    pub(crate) fn set_chunk_size(&mut self, chunk_size: Option<NonZeroUsize>) {
+        self.chunk_size = chunk_size;
+    }
+
+    /// Registers receiver of discarded items.
+    pub(crate) fn watch_discarded(&mut self, tx: OutboxSender<Vec<T>>) {
+        self.dead_letters.push(tx);
+    }
+
+    /// Records when the pending items are going to be emitted, see [`Shared::schedule`].
+    pub(crate) fn schedule(&self, flush_at: Option<Instant>) {
+        self.shared.schedule(flush_at);
+    }
+
+    /// Disconnects all the subscribers.
+    pub(crate) fn close(&self) {
+        self.shared.close();
+    }
+
+    fn push_quietly(&mut self, item: T) {
+        if let Some(wal) = &mut self.wal {
+            if let Err(e) = wal.append(&item) {
+                self.shared.report_wal_error(e);
+            }
+        }
+        self.batcher.observe(&item);
+        self.insert(item, Instant::now());
+    }
+
+    fn truncate_wal(&mut self) {
+        if let Some(wal) = &mut self.wal {
+            if let Err(e) = wal.truncate() {
+                self.shared.report_wal_error(e);
+            }
+        }
+    }
+
+    fn insert(&mut self, item: T, arrival: Instant) {
+        let before = self.items.len();
+        self.coalesce.push(&mut self.items, item);
+        // Replaced item keeps the arrival time of the older one.
+        if self.items.len() > before {
+            self.arrivals.push(arrival);
+        }
+    }
+
+    /// Removes all buffered items without emitting them and returns them, the log is emptied as
+    /// well.
+    pub(crate) fn drain(&mut self) -> Vec<T> {
+        let items = self.take();
+        self.batcher.reset();
+        self.truncate_wal();
+        items
+    }
+
+    fn take(&mut self) -> Vec<T> {
+        self.coalesce.reset();
+        self.arrivals.clear();
+        self.requeued = 0;
+        self.shared.set_pending_len(0);
+        self.shared.schedule(None);
+        take_keeping_room(&mut self.items, self.capacity)
+    }
+}
+
+impl<T, B> Buffer<T, B>
+where
+    T: Clone,
+{
+    /// Removes all buffered items without emitting them, the log is emptied as well. Removed
+    /// items are sent to the dead letters.
+    pub(crate) fn clear(&mut self) {
+        let items = self.drain();
+        self.discard(&items);
+    }
+
+    pub(crate) fn watch_dropped(&mut self, rx: OutboxReceiver<Vec<T>>) {
+        self.dropped.push(rx);
+        self.discard_dropped();
+    }
+
+    /// Discards the items of batches dropped by lagging receivers meanwhile.
+    fn discard_dropped(&mut self) {
+        let mut dropped = Vec::new();
+        self.dropped.retain(|rx| loop {
+            match rx.try_recv() {
+                Ok(items) => dropped.push(items),
+                Err(Error::Disconnected) => return false,
+                Err(_) => return true,
+            }
+        });
+        dropped.iter().for_each(|items| self.discard(items));
+    }
+
+    /// Sends discarded items to every dead letters receiver. Receivers which are gone are
+    /// forgotten.
+    pub(crate) fn discard(&mut self, items: &[T]) {
+        if !items.is_empty() {
+            self.dead_letters
+                .retain(|tx| tx.send(items.to_vec()).is_ok());
+        }
+    }
+}
+
+impl<T, B> Buffer<T, B>
+where
+    T: Clone,
+    B: Clone,
+{
+    /// Buffers all the items. With the capacity, they are emitted whenever it's reached, so no
+    /// more than `capacity` items are ever pending.
+    pub(crate) fn extend(&mut self, items: Vec<T>) {
+        for item in items {
+            self.push_quietly(item);
+            if self.is_full() {
+                self.emit();
+            }
+        }
+        self.shared.set_pending_len(self.items.len());
+    }
+
+    /// Sends buffered items to every subscriber.
+    pub(crate) fn emit(&mut self) {
+        self.emit_until(None);
+    }
+
+    /// Emits like [`Buffer::emit`], but waits for blocked subscribers only until the `deadline`.
+    /// Items which didn't get to all of them in time are sent to the dead letters. Returns the
+    /// number of such items.
+    fn emit_until(&mut self, deadline: Option<Instant>) -> usize {
+        if self.items.is_empty() {
+            return 0;
+        }
+        if let Some(gate) = &mut self.gate {
+            if gate.in_flight && self.shared.take_ack() {
+                gate.in_flight = false;
+            }
+            if gate.in_flight {
+                gate.held = true;
+                // Nothing is going to be emitted until the acknowledgement.
+                self.shared.schedule(None);
+                return 0;
+            }
+        }
+        let mut arrivals = take_keeping_room(&mut self.arrivals, self.capacity);
+        let requeued = self.requeued.min(self.items.len());
+        let mut items = self.take();
+        if let Some(filter) = &mut self.filter {
+            let mut keep = filter.keep(&items);
+            // Requeued items were not delivered, suppressing them would lose them.
+            keep.iter_mut().take(requeued).for_each(|keep| *keep = true);
+            retain_kept(&mut items, &keep);
+            retain_kept(&mut arrivals, &keep);
+        }
+        // Filtered out items are not going to be emitted, so they are done with as well.
+        let (delivered, late) = if items.is_empty() {
+            (true, Vec::new())
+        } else {
+            if let Some(gate) = &mut self.gate {
+                gate.in_flight = true;
+            }
+            self.publish(items, &arrivals, deadline)
+        };
+        self.batcher.reset();
+        if delivered {
+            self.truncate_wal();
+        }
+        self.discard(&late);
+        self.discard_dropped();
+        late.len()
+    }
+
+    /// Emits the items held back meanwhile when the emitted batch was acknowledged.
+    pub(crate) fn ack(&mut self) {
+        if let Some(gate) = &mut self.gate {
+            if gate.in_flight && self.shared.take_ack() {
+                gate.in_flight = false;
+                if mem::take(&mut gate.held) {
+                    self.emit();
+                }
+            }
+        }
+    }
+
+    /// Emits buffered items when `deadline` didn't pass yet, or there is none, otherwise drops
+    /// them as with [`Buffer::clear`]. Subscribers blocking the emission past the `deadline`
+    /// don't get the rest of the items, they are dropped as well. The number of dropped items is
+    /// sent to `reply`.
+    pub(crate) fn emit_by(&mut self, deadline: Option<Instant>, reply: Sender<usize>)
+    where
+        T: Clone,
+    {
+        let dropped = if deadline.is_none_or(|deadline| Instant::now() < deadline) {
+            self.emit_until(deadline)
+        } else {
+            let dropped = self.items.len();
+            self.clear();
+            dropped
+        };
+        let _ = reply.send(dropped);
+    }
+
+    /// Sends a copy of buffered items to every subscriber, leaving the buffer untouched.
+    pub(crate) fn emit_copy(&self)
+    where
+        T: Clone,
+    {
+        if !self.items.is_empty() {
+            self.publish(self.items.clone(), &self.arrivals, None);
+        }
+    }
+
+    /// Sends items to every subscriber, split into chunks when they don't fit in one batch.
+    /// Returns whether any subscriber got all of them, and the items of chunks some subscriber
+    /// didn't take before the `deadline`.
+    fn publish(
+        &self,
+        items: Vec<T>,
+        arrivals: &[Instant],
+        deadline: Option<Instant>,
+    ) -> (bool, Vec<T>) {
+        let chunk_size = match (self.chunk_size, self.capacity) {
+            (Some(chunk_size), Some(capacity)) => chunk_size.min(capacity).get(),
+            (limit, None) | (None, limit) => limit.map_or(items.len(), NonZeroUsize::get),
+        };
+        let mut arrivals = arrivals.chunks(chunk_size);
+        let mut delivered = true;
+        let mut late = Vec::new();
+        for items in into_chunks(items, chunk_size) {
+            let arrivals = arrivals.next().unwrap_or_default();
+            let Some(deadline) = deadline else {
+                self.shared.record_emission(arrivals);
+                delivered &= self.shared.publish(self.batcher.batch(items));
+                continue;
+            };
+            // Chunks left when the deadline passed are not even tried.
+            if !late.is_empty() || Instant::now() >= deadline {
+                late.extend(items);
+                continue;
+            }
+            self.shared.record_emission(arrivals);
+            let copy = items.clone();
+            match self
+                .shared
+                .publish_until(self.batcher.batch(items), Some(deadline))
+            {
+                Some(published) => delivered &= published,
+                None => late.extend(copy),
+            }
+        }
+        (delivered, late)
+    }
+}
+
+/// Takes all the values out. With the `capacity`, room for that many values is reserved again.
+fn take_keeping_room<T>(values: &mut Vec<T>, capacity: Option<NonZeroUsize>) -> Vec<T> {
+    match capacity {
+        Some(capacity) => mem::replace(values, Vec::with_capacity(capacity.get())),
+        None => mem::take(values),
+    }
+}
+
+fn retain_kept<T>(items: &mut Vec<T>, keep: &[bool]) {
+    let mut keep = keep.iter();
+    items.retain(|_| keep.next().copied().unwrap_or(true));
+}
+
+/// Splits items into consecutive chunks of `chunk_size` items, only the last one can be
+/// smaller. Items are moved, not cloned.
+pub(crate) fn into_chunks<T>(mut items: Vec<T>, chunk_size: usize) -> Vec<Vec<T>> {
+    let mut chunks = Vec::with_capacity(items.len().div_ceil(chunk_size));
+    // Splitting from the end moves each item only once.
+    while items.len() > chunk_size {
+        let last = (items.len() - 1) / chunk_size * chunk_size;
+        chunks.push(items.split_off(last));
+    }
+    chunks.push(items);
+    chunks.reverse();
+    chunks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reserved_room_is_kept_after_emitting() {
+        // given
+        let capacity = NonZeroUsize::new(4).unwrap();
+        let mut buffer = Buffer::new(|items: Vec<u32>| items).with_capacity(capacity);
+        let rx = buffer.shared().subscribe();
+
+        // when
+        buffer.extend(vec![1, 2]);
+        buffer.emit();
+        buffer.requeue(vec![1]);
+
+        // then
+        assert_eq!(rx.try_recv(), Ok(vec![1, 2]));
+        assert!(buffer.items.capacity() >= 4);
+        assert!(buffer.arrivals.capacity() >= 4);
+    }
+
+    #[test]
+    fn items_beyond_capacity_are_emitted_while_extending() {
+        // given
+        let capacity = NonZeroUsize::new(4).unwrap();
+        let mut buffer = Buffer::new(|items: Vec<u32>| items).with_capacity(capacity);
+        let rx = buffer.shared().subscribe();
+        let reserved = buffer.items.capacity();
+
+        // when
+        buffer.extend((1..=10).collect());
+
+        // then
+        assert_eq!(rx.try_recv(), Ok(vec![1, 2, 3, 4]));
+        assert_eq!(rx.try_recv(), Ok(vec![5, 6, 7, 8]));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(buffer.items(), &[9, 10]);
+        assert_eq!(buffer.items.capacity(), reserved);
+    }
+}
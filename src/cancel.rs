@@ -0,0 +1,113 @@
+use crate::sync::lock;
+use std::fmt::{self, Debug};
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+type Callback = Box<dyn FnOnce() + Send>;
+
+/// Token used to stop buffers from the outside, e.g. during coordinated shutdown of the service.
+///
+/// Buffers are attached to the token with [`CooldownSender::cancel_on`]. When the token is
+/// cancelled, every attached buffer emits its pending items and stops. Token can be cloned, all
+/// clones share the same state.
+///
+/// [`CooldownSender::cancel_on`]: crate::CooldownSender::cancel_on
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    cancelled: bool,
+    callbacks: Vec<Callback>,
+}
+
+impl CancellationToken {
+    /// Creates token which is not cancelled yet.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the token. Cancelling already cancelled token does nothing.
+    pub fn cancel(&self) {
+        let callbacks = {
+            let mut state = lock(&self.state);
+            state.cancelled = true;
+            mem::take(&mut state.callbacks)
+        };
+        callbacks.into_iter().for_each(|callback| callback());
+    }
+
+    /// Tells whether the token was cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        lock(&self.state).cancelled
+    }
+
+    /// Registers `callback` called once the token is cancelled, or right away when it already is.
+    pub(crate) fn on_cancel<F>(&self, callback: F)
+    where
+        F: 'static + FnOnce() + Send,
+    {
+        let mut state = lock(&self.state);
+        if state.cancelled {
+            drop(state);
+            callback();
+        } else {
+            state.callbacks.push(Box::new(callback));
+        }
+    }
+}
+
+impl Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{cooldown_buffer, Error};
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    #[test]
+    fn cancelled_token_flushes_and_stops_buffer() {
+        // given
+        let token = CancellationToken::new();
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        tx.cancel_on(&token);
+        tx.send_many(vec![1, 2]).unwrap();
+
+        // when
+        token.cancel();
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+        let after = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(buf, Ok(vec![1, 2]));
+        assert_eq!(after, Err(Error::Disconnected));
+        assert_eq!(tx.send(3), Err(Error::Disconnected));
+    }
+
+    #[test]
+    fn buffer_attached_to_cancelled_token_stops_right_away() {
+        // given
+        let token = CancellationToken::new();
+        token.cancel();
+        let (tx, rx) = cooldown_buffer::<u32>(channel(), Duration::from_secs(10));
+
+        // when
+        tx.cancel_on(&token);
+        let res = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert!(token.is_cancelled());
+        assert_eq!(res, Err(Error::Disconnected));
+    }
+}
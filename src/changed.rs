@@ -0,0 +1,69 @@
+use crate::buffer::Filter;
+use std::fmt::{self, Debug};
+
+/// Suppresses the batch which `same` considers equal to the previously emitted one, see
+/// [`cooldown_buffer_changed`](crate::cooldown_buffer_changed).
+pub(crate) struct Changed<T, F> {
+    same: F,
+    previous: Option<Vec<T>>,
+}
+
+impl<T, F> Changed<T, F> {
+    pub(crate) fn new(same: F) -> Self {
+        Self {
+            same,
+            previous: None,
+        }
+    }
+}
+
+impl<T, F> Filter<T> for Changed<T, F>
+where
+    T: Clone + Debug + Send,
+    F: Fn(&[T], &[T]) -> bool + Send,
+{
+    fn keep(&mut self, items: &[T]) -> Vec<bool> {
+        let unchanged = self
+            .previous
+            .as_deref()
+            .is_some_and(|previous| (self.same)(previous, items));
+        if !unchanged {
+            self.previous = Some(items.to_vec());
+        }
+        vec![!unchanged; items.len()]
+    }
+}
+
+impl<T, F> Debug for Changed<T, F>
+where
+    T: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Changed")
+            .field("previous", &self.previous)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_batch_equal_to_previous_is_suppressed() {
+        // given
+        let mut changed = Changed::new(|previous: &[u32], batch: &[u32]| previous == batch);
+
+        // when
+        let first = changed.keep(&[1, 2]);
+        let same = changed.keep(&[1, 2]);
+        let other = changed.keep(&[2, 1]);
+        let back = changed.keep(&[1, 2]);
+
+        // then
+        assert_eq!(first, vec![true, true]);
+        assert_eq!(same, vec![false, false]);
+        assert_eq!(other, vec![true, true]);
+        assert_eq!(back, vec![true, true]);
+    }
+}
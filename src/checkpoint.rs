@@ -0,0 +1,99 @@
+/// Copy of items pending in the buffer, created with [`CooldownSender::checkpoint`].
+///
+/// It can be put back into a buffer (possibly a new one) with [`CooldownSender::restore`]. With
+/// `serde` feature enabled, it can be serialized when `T` can be, e.g. to keep in-flight items
+/// during service restart.
+///
+/// [`CooldownSender::checkpoint`]: crate::CooldownSender::checkpoint
+/// [`CooldownSender::restore`]: crate::CooldownSender::restore
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint<T> {
+    items: Vec<T>,
+}
+
+impl<T> Checkpoint<T> {
+    pub(crate) fn new(items: Vec<T>) -> Self {
+        Self { items }
+    }
+
+    /// Returns items captured in the checkpoint.
+    #[must_use]
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Consumes the checkpoint and returns captured items.
+    #[must_use]
+    pub fn into_items(self) -> Vec<T> {
+        self.items
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for Checkpoint<T>
+where
+    T: serde::Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.items.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for Checkpoint<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Vec::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cooldown_buffer;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn checkpoint_restores_pending_items_into_new_buffer() {
+        // given
+        let (old_tx, _old_rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        let (new_tx, new_rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+        old_tx.send_many(vec![1, 2]).unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        // when
+        let checkpoint = old_tx.checkpoint();
+        new_tx.restore(checkpoint).unwrap();
+        let buf = new_rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(old_tx.pending_len(), 2);
+        assert_eq!(buf, Ok(vec![1, 2]));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn checkpoint_can_be_deserialized() {
+        use super::Checkpoint;
+        use serde::de::value::{Error, SeqDeserializer};
+        use serde::Deserialize;
+
+        // given
+        let deserializer = SeqDeserializer::<_, Error>::new(vec![1, 2].into_iter());
+
+        // when
+        let checkpoint = Checkpoint::<u32>::deserialize(deserializer);
+
+        // then
+        assert_eq!(checkpoint.map(Checkpoint::into_items), Ok(vec![1, 2]));
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+
+/// Decides what happens with the item pushed to the buffer.
+pub(crate) enum Coalesce<T> {
+    /// Every item is kept.
+    All,
+    /// Only the most recent item is kept.
+    Latest,
+    /// Only the most recent item with a given key is kept, at the position of the first one.
+    ByKey(Box<dyn Index<T> + Send>),
+}
+
+impl<T> Coalesce<T> {
+    pub(crate) fn by_key<K, F>(key: F) -> Self
+    where
+        T: 'static,
+        K: 'static + Eq + Hash + Send,
+        F: 'static + Fn(&T) -> K + Send,
+    {
+        Self::ByKey(Box::new(KeyIndex {
+            key,
+            slots: HashMap::new(),
+        }))
+    }
+
+    pub(crate) fn push(&mut self, items: &mut Vec<T>, item: T) {
+        match self {
+            Self::All => items.push(item),
+            Self::Latest => {
+                items.clear();
+                items.push(item);
+            }
+            Self::ByKey(index) => match index.slot(&item, items.len()) {
+                Some(slot) => items[slot] = item,
+                None => items.push(item),
+            },
+        }
+    }
+
+    /// Forgets everything known about the items, called when the buffer is emptied.
+    pub(crate) fn reset(&mut self) {
+        if let Self::ByKey(index) = self {
+            index.clear();
+        }
+    }
+}
+
+impl<T> Debug for Coalesce<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::All => write!(f, "All"),
+            Self::Latest => write!(f, "Latest"),
+            Self::ByKey(_) => write!(f, "ByKey"),
+        }
+    }
+}
+
+/// Position of items in the buffer by their key. Hides the type of the key.
+pub(crate) trait Index<T> {
+    /// Returns position of the item with the same key, or remembers that the item is going to be
+    /// at position `next`.
+    fn slot(&mut self, item: &T, next: usize) -> Option<usize>;
+
+    fn clear(&mut self);
+}
+
+struct KeyIndex<K, F> {
+    key: F,
+    slots: HashMap<K, usize>,
+}
+
+impl<T, K, F> Index<T> for KeyIndex<K, F>
+where
+    K: Eq + Hash,
+    F: Fn(&T) -> K,
+{
+    fn slot(&mut self, item: &T, next: usize) -> Option<usize> {
+        match self.slots.entry((self.key)(item)) {
+            Entry::Occupied(slot) => Some(*slot.get()),
+            Entry::Vacant(slot) => {
+                slot.insert(next);
+                None
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.slots.clear();
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::mpsc::Receiver;
+use std::time::Instant;
+
+/// Gathers all the items arriving through `rx` until the `deadline`. It's a one-shot alternative
+/// to [`cooldown_buffer`](crate::cooldown_buffer) when you just need "everything that arrives in
+/// the next two seconds". Returns earlier when all the senders are dropped.
+///
+/// # Example
+/// ```
+/// use std::time::{Duration, Instant};
+/// use cooldown_buffer::collect_until;
+/// use std::sync::mpsc::channel;
+///
+/// let (tx, rx) = channel();
+/// tx.send(1).unwrap();
+/// tx.send(2).unwrap();
+///
+/// let items = collect_until(&rx, Instant::now() + Duration::from_millis(50));
+/// assert_eq!(items, vec![1, 2]);
+/// ```
+pub fn collect_until<T>(rx: &Receiver<T>, deadline: Instant) -> Vec<T> {
+    let mut items = Vec::new();
+    loop {
+        // Items keep coming with steady producer, so the deadline can't be left to the timeout.
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return items;
+        }
+        match rx.recv_timeout(left) {
+            Ok(item) => items.push(item),
+            Err(_) => return items,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn items_after_deadline_are_not_collected() {
+        // given
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            tx.send(1).unwrap();
+            thread::sleep(Duration::from_millis(150));
+            tx.send(2).unwrap();
+        });
+
+        // when
+        let items = collect_until(&rx, Instant::now() + Duration::from_millis(100));
+
+        // then
+        assert_eq!(items, vec![1]);
+    }
+
+    #[test]
+    fn steady_producer_does_not_extend_deadline() {
+        // given
+        let (tx, rx) = channel();
+        thread::spawn(move || while tx.send(1).is_ok() {});
+        let start = Instant::now();
+
+        // when
+        let items = collect_until(&rx, start + Duration::from_millis(50));
+
+        // then
+        assert!(!items.is_empty());
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn collecting_ends_when_senders_are_dropped() {
+        // given
+        let (tx, rx) = channel();
+        tx.send(1).unwrap();
+        drop(tx);
+        let start = Instant::now();
+
+        // when
+        let items = collect_until(&rx, start + Duration::from_secs(10));
+
+        // then
+        assert_eq!(items, vec![1]);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
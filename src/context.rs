@@ -0,0 +1,69 @@
+use crate::buffer::Batcher;
+use std::fmt::{self, Debug};
+
+/// Batch emitted by [`cooldown_buffer_with_context`](crate::cooldown_buffer_with_context),
+/// together with the context accumulated from its items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextBatch<T, C> {
+    /// Emitted items.
+    pub items: Vec<T>,
+
+    /// Context accumulated from all the items received since the last batch.
+    pub context: C,
+}
+
+impl<T, C> AsRef<[T]> for ContextBatch<T, C> {
+    fn as_ref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+/// Accumulates the context while the items are buffered.
+pub(crate) struct Accumulate<C, F> {
+    context: C,
+    accumulate: F,
+}
+
+impl<C, F> Accumulate<C, F>
+where
+    C: Default,
+{
+    pub(crate) fn new(accumulate: F) -> Self {
+        Self {
+            context: C::default(),
+            accumulate,
+        }
+    }
+}
+
+impl<T, C, F> Batcher<T, ContextBatch<T, C>> for Accumulate<C, F>
+where
+    C: Clone + Debug + Default + Send,
+    F: Fn(&mut C, &T) + Send,
+{
+    fn observe(&mut self, item: &T) {
+        (self.accumulate)(&mut self.context, item);
+    }
+
+    fn batch(&self, items: Vec<T>) -> ContextBatch<T, C> {
+        ContextBatch {
+            items,
+            context: self.context.clone(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.context = C::default();
+    }
+}
+
+impl<C, F> Debug for Accumulate<C, F>
+where
+    C: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Accumulate")
+            .field("context", &self.context)
+            .finish_non_exhaustive()
+    }
+}
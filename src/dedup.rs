@@ -0,0 +1,79 @@
+use crate::buffer::Filter;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// Suppresses items with the same key as the item emitted within the last `window`, see
+/// [`cooldown_buffer_dedup`](crate::cooldown_buffer_dedup).
+pub(crate) struct Dedup<K, F> {
+    key: F,
+    window: Duration,
+    /// When the item with the key was emitted.
+    emitted: HashMap<K, Instant>,
+}
+
+impl<K, F> Dedup<K, F> {
+    pub(crate) fn new(window: Duration, key: F) -> Self {
+        Self {
+            key,
+            window,
+            emitted: HashMap::new(),
+        }
+    }
+}
+
+impl<T, K, F> Filter<T> for Dedup<K, F>
+where
+    K: Eq + Hash + Send,
+    F: Fn(&T) -> K + Send,
+{
+    fn keep(&mut self, items: &[T]) -> Vec<bool> {
+        let now = Instant::now();
+        self.emitted
+            .retain(|_, emitted| now.saturating_duration_since(*emitted) < self.window);
+        items
+            .iter()
+            .map(|item| {
+                let key = (self.key)(item);
+                let seen = self.emitted.contains_key(&key);
+                if !seen {
+                    self.emitted.insert(key, now);
+                }
+                !seen
+            })
+            .collect()
+    }
+}
+
+impl<K, F> Debug for Dedup<K, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dedup")
+            .field("window", &self.window)
+            .field("emitted", &self.emitted.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn keys_are_forgotten_after_window() {
+        // given
+        let mut dedup = Dedup::new(Duration::from_millis(50), |n: &u32| n % 10);
+
+        // when
+        let first = dedup.keep(&[1, 2, 11]);
+        let second = dedup.keep(&[2, 3]);
+        thread::sleep(Duration::from_millis(60));
+        let third = dedup.keep(&[1]);
+
+        // then
+        assert_eq!(first, vec![true, true, false]);
+        assert_eq!(second, vec![false, true]);
+        assert_eq!(third, vec![true]);
+    }
+}
@@ -0,0 +1,21 @@
+use thiserror::Error;
+
+/// Errors returned by the cooldown buffer handles.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The other side of the buffer is gone, so the operation can never succeed.
+    #[error("the cooldown buffer is disconnected")]
+    Disconnected,
+
+    /// No batch was emitted, or the buffer didn't respond, within the given time.
+    #[error("timed out waiting for a batch")]
+    Timeout,
+
+    /// No batch is waiting to be received.
+    #[error("no batch is waiting to be received")]
+    Empty,
+
+    /// The item doesn't belong to any route of the [`Router`](crate::Router).
+    #[error("no route for the item")]
+    NoRoute,
+}
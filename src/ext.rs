@@ -0,0 +1,68 @@
+use crate::cooldown_buffer_unbounded;
+use crate::receiver::IntoIter;
+use std::fmt::Debug;
+use std::thread;
+use std::time::Duration;
+
+/// Extension of iterators allowing to batch their items with the cooldown buffer.
+pub trait IteratorExt: Iterator + Sized {
+    /// Consumes the iterator on a background thread and returns an iterator of batches, as
+    /// emitted by [`cooldown_buffer`](crate::cooldown_buffer). Items are grouped by gaps between
+    /// their arrival, so it makes sense for iterators blocking on each item, e.g. iterating
+    /// over the events of some source.
+    ///
+    /// When the iterator ends, pending items are emitted right away and the batches end as well.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use cooldown_buffer::IteratorExt;
+    ///
+    /// let batches: Vec<_> = (1..=3).cooldown_batches(Duration::from_millis(20)).collect();
+    /// assert_eq!(batches, vec![vec![1, 2, 3]]);
+    /// ```
+    fn cooldown_batches(self, cooldown_time: Duration) -> IntoIter<Vec<Self::Item>>;
+}
+
+impl<I> IteratorExt for I
+where
+    I: 'static + Iterator + Send,
+    I::Item: 'static + Clone + Debug + Send,
+{
+    fn cooldown_batches(self, cooldown_time: Duration) -> IntoIter<Vec<Self::Item>> {
+        let (tx, rx) = cooldown_buffer_unbounded(cooldown_time);
+        thread::spawn(move || {
+            for item in self {
+                if tx.send(item).is_err() {
+                    break;
+                }
+            }
+        });
+        rx.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::iter;
+
+    #[test]
+    fn items_are_grouped_by_arrival_gaps() {
+        // given
+        let mut n = 0;
+        let slow = iter::from_fn(move || {
+            n += 1;
+            if n == 3 {
+                thread::sleep(Duration::from_millis(150));
+            }
+            (n <= 4).then_some(n)
+        });
+
+        // when
+        let batches: Vec<_> = slow.cooldown_batches(Duration::from_millis(50)).collect();
+
+        // then
+        assert_eq!(batches, vec![vec![1, 2], vec![3, 4]]);
+    }
+}
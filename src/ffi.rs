@@ -0,0 +1,219 @@
+//! C bindings. Buffers and batches are opaque handles, items are byte payloads copied into the
+//! buffer. Every handle must be freed with its `_free` function.
+//!
+//! The prototypes for C and C++ are in `include/cooldown_buffer.h`.
+
+use crate::{cooldown_buffer, BatchReceiver, CooldownSender, Error};
+use std::ptr;
+use std::slice;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+/// Buffer created with [`cooldown_buffer_new`].
+#[derive(Debug)]
+pub struct FfiBuffer {
+    tx: CooldownSender<Vec<u8>>,
+    rx: BatchReceiver<Vec<u8>>,
+}
+
+/// Batch received with [`cooldown_buffer_recv`].
+#[derive(Debug)]
+pub struct FfiBatch {
+    items: Vec<Vec<u8>>,
+}
+
+/// Creates the buffer cooling down after `cooldown_ms` milliseconds, see [`cooldown_buffer`].
+#[no_mangle]
+pub extern "C" fn cooldown_buffer_new(cooldown_ms: u64) -> *mut FfiBuffer {
+    let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(cooldown_ms));
+    Box::into_raw(Box::new(FfiBuffer { tx, rx }))
+}
+
+/// Copies `len` bytes from `data` and sends them to the buffer as a single item. Returns `0` on
+/// success and `-1` when the buffer is not running anymore.
+///
+/// # Safety
+///
+/// `buffer` must come from [`cooldown_buffer_new`] and not be freed yet. `data` must point to
+/// `len` readable bytes, it can be null when `len` is zero.
+#[no_mangle]
+pub unsafe extern "C" fn cooldown_buffer_send(
+    buffer: *const FfiBuffer,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    let item = if len == 0 {
+        Vec::new()
+    } else {
+        slice::from_raw_parts(data, len).to_vec()
+    };
+    status((&*buffer).tx.send(item).is_ok())
+}
+
+/// Emits buffered items right away. Returns `0` on success and `-1` when the buffer is not
+/// running anymore.
+///
+/// # Safety
+///
+/// `buffer` must come from [`cooldown_buffer_new`] and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn cooldown_buffer_flush(buffer: *const FfiBuffer) -> i32 {
+    status((&*buffer).tx.flush().is_ok())
+}
+
+/// Waits at most `timeout_ms` milliseconds for the next batch. Returns null when there is no
+/// batch, then `status` tells why: `1` when no batch was emitted in time and `-1` when the buffer
+/// is not running anymore. It's `0` when the batch is returned.
+///
+/// # Safety
+///
+/// `buffer` must come from [`cooldown_buffer_new`] and not be freed yet. `status` must point to
+/// writable `int32_t`, or be null when the reason is not needed.
+#[no_mangle]
+pub unsafe extern "C" fn cooldown_buffer_recv(
+    buffer: *const FfiBuffer,
+    timeout_ms: u64,
+    status: *mut i32,
+) -> *mut FfiBatch {
+    let (batch, code) = match (&*buffer)
+        .rx
+        .recv_timeout(Duration::from_millis(timeout_ms))
+    {
+        Ok(items) => (Box::into_raw(Box::new(FfiBatch { items })), 0),
+        Err(Error::Disconnected) => (ptr::null_mut(), -1),
+        Err(_) => (ptr::null_mut(), 1),
+    };
+    if !status.is_null() {
+        *status = code;
+    }
+    batch
+}
+
+/// Frees the buffer, discarding pending items. Call [`cooldown_buffer_flush`] and
+/// [`cooldown_buffer_recv`] first to get them. Batches received before stay valid.
+///
+/// # Safety
+///
+/// `buffer` must come from [`cooldown_buffer_new`] and not be freed yet, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn cooldown_buffer_free(buffer: *mut FfiBuffer) {
+    if !buffer.is_null() {
+        drop(Box::from_raw(buffer));
+    }
+}
+
+/// Returns the number of items in the batch.
+///
+/// # Safety
+///
+/// `batch` must come from [`cooldown_buffer_recv`] and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn cooldown_batch_len(batch: *const FfiBatch) -> usize {
+    (&*batch).items.len()
+}
+
+/// Returns the item at `index` and writes its length to `len`. Returns null when there is no
+/// such item. The item is valid until the batch is freed.
+///
+/// # Safety
+///
+/// `batch` must come from [`cooldown_buffer_recv`] and not be freed yet. `len` must point to
+/// writable `size_t`.
+#[no_mangle]
+pub unsafe extern "C" fn cooldown_batch_item(
+    batch: *const FfiBatch,
+    index: usize,
+    len: *mut usize,
+) -> *const u8 {
+    match (&*batch).items.get(index) {
+        Some(item) => {
+            *len = item.len();
+            item.as_ptr()
+        }
+        None => ptr::null(),
+    }
+}
+
+/// Frees the batch.
+///
+/// # Safety
+///
+/// `batch` must come from [`cooldown_buffer_recv`] and not be freed yet, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn cooldown_batch_free(batch: *mut FfiBatch) {
+    if !batch.is_null() {
+        drop(Box::from_raw(batch));
+    }
+}
+
+fn status(ok: bool) -> i32 {
+    if ok {
+        0
+    } else {
+        -1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn payloads_are_batched_through_c_api() {
+        // given
+        let buffer = cooldown_buffer_new(10_000);
+
+        // when
+        let batch = unsafe {
+            cooldown_buffer_send(buffer, b"ab".as_ptr(), 2);
+            cooldown_buffer_send(buffer, ptr::null(), 0);
+            cooldown_buffer_flush(buffer);
+            cooldown_buffer_recv(buffer, 500, ptr::null_mut())
+        };
+
+        // then
+        let mut len = 0;
+        unsafe {
+            assert!(!batch.is_null());
+            assert_eq!(cooldown_batch_len(batch), 2);
+            let first = cooldown_batch_item(batch, 0, &mut len);
+            assert_eq!(slice::from_raw_parts(first, len), b"ab");
+            assert!(!cooldown_batch_item(batch, 1, &mut len).is_null());
+            assert_eq!(len, 0);
+            assert!(cooldown_batch_item(batch, 2, &mut len).is_null());
+            cooldown_batch_free(batch);
+            cooldown_buffer_free(buffer);
+        }
+    }
+
+    #[test]
+    fn recv_gives_null_when_nothing_is_emitted() {
+        // given
+        let buffer = cooldown_buffer_new(10_000);
+        let mut status = 0;
+
+        // when
+        let batch = unsafe { cooldown_buffer_recv(buffer, 10, &mut status) };
+
+        // then
+        assert!(batch.is_null());
+        assert_eq!(status, 1);
+        unsafe { cooldown_buffer_free(buffer) };
+    }
+
+    #[test]
+    fn recv_tells_when_buffer_is_not_running() {
+        // given
+        let buffer = cooldown_buffer_new(10_000);
+        let mut status = 0;
+        unsafe { (&*buffer).tx.shutdown_timeout(Duration::from_secs(1)) }.unwrap();
+
+        // when
+        let batch = unsafe { cooldown_buffer_recv(buffer, 500, &mut status) };
+
+        // then
+        assert!(batch.is_null());
+        assert_eq!(status, -1);
+        unsafe { cooldown_buffer_free(buffer) };
+    }
+}
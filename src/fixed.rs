@@ -0,0 +1,58 @@
+use std::ops::Deref;
+
+/// Batch of at most `N` items stored inline, emitted by
+/// [`cooldown_buffer_fixed`](crate::cooldown_buffer_fixed). It dereferences to the slice of
+/// emitted items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedBatch<T, const N: usize> {
+    items: [T; N],
+    len: usize,
+}
+
+impl<T, const N: usize> FixedBatch<T, N>
+where
+    T: Copy + Default,
+{
+    /// Copies `items` into the batch. The buffer never emits more than `N` items at once.
+    pub(crate) fn from_items(items: Vec<T>) -> Self {
+        assert!(items.len() <= N, "batch has more than {N} items");
+        let mut batch = Self {
+            items: [T::default(); N],
+            len: items.len(),
+        };
+        batch.items[..items.len()].copy_from_slice(&items);
+        batch
+    }
+}
+
+impl<T, const N: usize> Deref for FixedBatch<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.items[..self.len]
+    }
+}
+
+impl<T, const N: usize> AsRef<[T]> for FixedBatch<T, N> {
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn batch_holds_only_given_items() {
+        // given
+        let items = vec![1, 2];
+
+        // when
+        let batch = FixedBatch::<u8, 4>::from_items(items);
+
+        // then
+        assert_eq!(batch.len(), 2);
+        assert_eq!(&*batch, &[1, 2]);
+    }
+}
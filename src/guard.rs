@@ -0,0 +1,77 @@
+use crate::sender::CooldownSender;
+use std::ops::Deref;
+
+/// Guard flushing the buffer when dropped, created with [`CooldownSender::flush_guard`].
+///
+/// It's dropped also during panic unwinding, so items sent before the panic are not stuck in the
+/// buffer until the cooldown passes. The guard dereferences to [`CooldownSender`], so it can be
+/// used for sending items directly.
+#[derive(Debug)]
+pub struct FlushGuard<T, B = Vec<T>> {
+    sender: CooldownSender<T, B>,
+}
+
+impl<T, B> FlushGuard<T, B> {
+    pub(crate) fn new(sender: CooldownSender<T, B>) -> Self {
+        Self { sender }
+    }
+}
+
+impl<T, B> Deref for FlushGuard<T, B> {
+    type Target = CooldownSender<T, B>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.sender
+    }
+}
+
+impl<T, B> Drop for FlushGuard<T, B> {
+    fn drop(&mut self) {
+        // Nothing can be done if the buffer is gone, and panicking here could abort the process
+        // when the guard is dropped during unwinding.
+        let _ = self.sender.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cooldown_buffer;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn dropped_guard_flushes_buffer() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+
+        // when
+        {
+            let guard = tx.flush_guard();
+            guard.send(1).unwrap();
+        }
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(buf, Ok(vec![1]));
+    }
+
+    #[test]
+    fn guard_flushes_buffer_on_panic() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        let guard = tx.flush_guard();
+
+        // when
+        let res = thread::spawn(move || {
+            guard.send(1).unwrap();
+            panic!("producer failed");
+        })
+        .join();
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert!(res.is_err());
+        assert_eq!(buf, Ok(vec![1]));
+    }
+}
@@ -0,0 +1,181 @@
+use crate::buffer::Buffer;
+use crate::error::Error;
+use crate::outbox::OutboxReceiver;
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// Cooldown buffer running on the caller's thread with a virtual clock. It behaves like
+/// [`cooldown_buffer`](crate::cooldown_buffer), but the time passes only when
+/// [`InlineBuffer::advance`] is called, so batching can be tested without any sleeps.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use cooldown_buffer::InlineBuffer;
+///
+/// let mut buffer = InlineBuffer::new(Duration::from_millis(100));
+/// buffer.send(1);
+/// buffer.advance(Duration::from_millis(90));
+/// buffer.send(2);
+/// buffer.advance(Duration::from_millis(90));
+/// assert!(buffer.try_recv().is_err());
+///
+/// buffer.advance(Duration::from_millis(10));
+/// assert_eq!(buffer.try_recv(), Ok(vec![1, 2]));
+/// ```
+#[derive(Debug)]
+pub struct InlineBuffer<T> {
+    buffer: Buffer<T, Vec<T>>,
+    buffered_rx: OutboxReceiver<Vec<T>>,
+    cooldown_time: Duration,
+    elapsed: Duration,
+    deadline: Option<Duration>,
+}
+
+impl<T> InlineBuffer<T>
+where
+    T: Clone + Debug,
+{
+    /// Creates empty buffer cooling down after `cooldown_time` of the virtual time.
+    #[must_use]
+    pub fn new(cooldown_time: Duration) -> Self {
+        let buffer = Buffer::new(|items| items);
+        let buffered_rx = buffer.shared().subscribe();
+        Self {
+            buffer,
+            buffered_rx,
+            cooldown_time,
+            elapsed: Duration::ZERO,
+            deadline: None,
+        }
+    }
+
+    /// Buffers single item and restarts the cooldown.
+    pub fn send(&mut self, item: T) {
+        self.buffer.push(item);
+        self.restart();
+    }
+
+    /// Buffers all the items as a single activity, see
+    /// [`CooldownSender::send_batch`](crate::CooldownSender::send_batch).
+    pub fn send_batch(&mut self, items: Vec<T>) {
+        if !items.is_empty() {
+            self.buffer.extend(items);
+            self.restart();
+        }
+    }
+
+    /// Emits buffered items right away, without waiting for the cooldown.
+    pub fn flush(&mut self) {
+        self.buffer.emit();
+        self.deadline = None;
+    }
+
+    /// Drops buffered items without emitting them and stops the cooldown.
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.deadline = None;
+    }
+
+    /// Changes the cooldown time. The new value is used starting from the next item.
+    pub fn set_cooldown(&mut self, cooldown_time: Duration) {
+        self.cooldown_time = cooldown_time;
+    }
+
+    /// Moves the virtual clock forward. Buffered items are emitted when the buffer cooled down in
+    /// the meantime.
+    pub fn advance(&mut self, by: Duration) {
+        self.elapsed += by;
+        if self
+            .deadline
+            .is_some_and(|deadline| deadline <= self.elapsed)
+        {
+            self.flush();
+        }
+    }
+
+    /// Returns the virtual time passed since the buffer was created.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the number of items buffered and not yet emitted.
+    #[must_use]
+    pub fn pending_len(&self) -> usize {
+        self.buffer.items().len()
+    }
+
+    /// Returns the next emitted batch.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Empty`] when no batch was emitted.
+    pub fn try_recv(&self) -> Result<Vec<T>, Error> {
+        self.buffered_rx.try_recv()
+    }
+
+    fn restart(&mut self) {
+        if self.cooldown_time.is_zero() {
+            self.flush();
+        } else {
+            self.deadline = Some(self.elapsed + self.cooldown_time);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn batches_follow_virtual_clock() {
+        // given
+        let mut buffer = InlineBuffer::new(Duration::from_secs(60));
+
+        // when
+        buffer.send(1);
+        buffer.advance(Duration::from_secs(59));
+        let cooling = buffer.try_recv();
+        buffer.advance(Duration::from_secs(1));
+        let cooled = buffer.try_recv();
+
+        // then
+        assert_eq!(cooling, Err(Error::Empty));
+        assert_eq!(cooled, Ok(vec![1]));
+        assert_eq!(buffer.elapsed(), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn every_item_restarts_virtual_cooldown() {
+        // given
+        let mut buffer = InlineBuffer::new(Duration::from_millis(100));
+
+        // when
+        for n in 1..=3 {
+            buffer.send(n);
+            buffer.advance(Duration::from_millis(90));
+        }
+        let pending = buffer.pending_len();
+        buffer.advance(Duration::from_millis(10));
+
+        // then
+        assert_eq!(pending, 3);
+        assert_eq!(buffer.try_recv(), Ok(vec![1, 2, 3]));
+        assert_eq!(buffer.try_recv(), Err(Error::Empty));
+    }
+
+    #[test]
+    fn clear_stops_virtual_cooldown() {
+        // given
+        let mut buffer = InlineBuffer::new(Duration::from_millis(100));
+
+        // when
+        buffer.send_batch(vec![1, 2]);
+        buffer.clear();
+        buffer.advance(Duration::from_secs(1));
+
+        // then
+        assert_eq!(buffer.try_recv(), Err(Error::Empty));
+    }
+}
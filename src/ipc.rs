@@ -0,0 +1,199 @@
+use crate::sender::CooldownSender;
+use crate::wal::{frame, invalid_data};
+use std::fmt::{self, Debug};
+use std::io::{self, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::thread::{self, JoinHandle};
+
+/// Default limit of the encoded item size accepted by [`forward_frames`], 16 MiB.
+pub const MAX_FRAME: usize = 16 << 20;
+
+/// Writes items to a byte stream (e.g. stdout of the child process or a socket), so another
+/// process can feed them to its buffer with [`forward_frames`].
+///
+/// Each item is written as bytes returned from `encode`, prefixed with their length. That's the
+/// same format as used by [`Wal`](crate::Wal).
+pub struct FrameWriter<W, T> {
+    writer: W,
+    encode: fn(&T) -> Vec<u8>,
+    _item: PhantomData<fn(&T)>,
+}
+
+impl<W, T> FrameWriter<W, T>
+where
+    W: Write,
+{
+    /// Creates writer encoding items with `encode`.
+    pub fn new(writer: W, encode: fn(&T) -> Vec<u8>) -> Self {
+        Self {
+            writer,
+            encode,
+            _item: PhantomData,
+        }
+    }
+
+    /// Writes the item and flushes the stream, so the other side gets it right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns error when the stream can't be written, or when encoded item is bigger than
+    /// `u32::MAX` bytes.
+    pub fn send(&mut self, item: &T) -> io::Result<()> {
+        let frame = frame(&(self.encode)(item)).ok_or_else(|| invalid_data("item too big"))?;
+        self.writer.write_all(&frame)?;
+        self.writer.flush()
+    }
+}
+
+impl<W, T> Debug for FrameWriter<W, T>
+where
+    W: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameWriter")
+            .field("writer", &self.writer)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Reads items written by [`FrameWriter`] from `reader` on a background thread and sends them to
+/// the buffer. It's the receiving side of cross-process buffering - e.g. the parent process
+/// forwards stdout of each child process and gets batches of items coming from all of them.
+///
+/// The thread ends when the stream ends or when the buffer stops. It returns error when the
+/// stream can't be read, ends in the middle of an item or when `decode` returns `None`. Items
+/// bigger than [`MAX_FRAME`] are treated as a corrupted stream, e.g. when something else writes to
+/// it too, and the thread returns [`ErrorKind::InvalidData`] error. Use
+/// [`forward_frames_limited`] for different limit.
+pub fn forward_frames<R, T, B>(
+    reader: R,
+    decode: fn(&[u8]) -> Option<T>,
+    sender: CooldownSender<T, B>,
+) -> JoinHandle<io::Result<()>>
+where
+    R: 'static + Read + Send,
+    T: 'static + Send,
+    B: 'static + Send,
+{
+    forward_frames_limited(reader, decode, sender, MAX_FRAME)
+}
+
+/// Works like [`forward_frames`], but accepts items of up to `max_frame` encoded bytes.
+pub fn forward_frames_limited<R, T, B>(
+    mut reader: R,
+    decode: fn(&[u8]) -> Option<T>,
+    sender: CooldownSender<T, B>,
+    max_frame: usize,
+) -> JoinHandle<io::Result<()>>
+where
+    R: 'static + Read + Send,
+    T: 'static + Send,
+    B: 'static + Send,
+{
+    thread::spawn(move || {
+        while let Some(record) = read_frame(&mut reader, max_frame)? {
+            let item = decode(&record).ok_or_else(|| invalid_data("undecodable frame"))?;
+            if sender.send(item).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Returns `None` when the stream ended cleanly, before the next frame.
+fn read_frame<R: Read>(reader: &mut R, max_frame: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len[..1]) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    reader.read_exact(&mut len[1..])?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > max_frame {
+        return Err(invalid_data("frame too big"));
+    }
+    let mut record = vec![0; len];
+    reader.read_exact(&mut record)?;
+    Ok(Some(record))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cooldown_buffer;
+    use std::io::Cursor;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    fn encode(item: &u32) -> Vec<u8> {
+        item.to_le_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<u32> {
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    #[test]
+    fn written_items_are_buffered_on_the_other_side() {
+        // given
+        let mut writer = FrameWriter::new(Vec::new(), encode);
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+
+        // when
+        writer.send(&1).unwrap();
+        writer.send(&2).unwrap();
+        let done = forward_frames(Cursor::new(writer.writer), decode, tx);
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert!(done.join().unwrap().is_ok());
+        assert_eq!(buf, Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn garbage_header_is_an_error() {
+        // given
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+
+        // when
+        let done = forward_frames(Cursor::new(b"oops, not a frame".to_vec()), decode, tx);
+
+        // then
+        let err = done.join().unwrap().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn frames_over_the_limit_are_an_error() {
+        // given
+        let mut writer = FrameWriter::new(Vec::new(), encode);
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+
+        // when
+        writer.send(&1).unwrap();
+        let done = forward_frames_limited(Cursor::new(writer.writer), decode, tx, 3);
+
+        // then
+        let err = done.join().unwrap().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn stream_ending_in_the_middle_of_item_is_an_error() {
+        // given
+        let mut writer = FrameWriter::new(Vec::new(), encode);
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+
+        // when
+        writer.send(&1).unwrap();
+        let mut bytes = writer.writer;
+        bytes.pop();
+        let done = forward_frames(Cursor::new(bytes), decode, tx);
+
+        // then
+        let err = done.join().unwrap().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+}
@@ -45,72 +45,519 @@
 //! # Details
 //!
 //! It uses one thread and two channels. First channel is used to send and receive single items.
-//! The Sender of this channel is handed to the user. Second channel is used to send and receive
-//! vector of items (buffered items). Its Receiver is handed to the user.
+//! Its Sender is wrapped in [`CooldownSender`] and handed to the user. Second channel is used to
+//! send and receive vector of items (buffered items). Its Receiver is wrapped in [`BatchReceiver`]
+//! and handed to the user.
 //!
-//! The thread receives items one at a time and pushes it to the vector. It then stops the timer
-//! and starts it again each time (this lib is using [ThreadTimer](thread_timer::ThreadTimer)).
-//! If the timer wasn't running then the error is ignored. If no more item appears for a specified
-//! amount of time, the timer sends the buffered items via second channel and clears the vector to
-//! make it ready for next buffering.
+//! The thread receives items one at a time and pushes it to the vector. Then it waits for the next
+//! item, but no longer than the cooldown time. If no more item appears for a specified amount of
+//! time, the thread sends the buffered items via second channel and clears the vector to make it
+//! ready for next buffering. The vector is owned by the thread alone, handles only read the
+//! numbers it publishes (like [`CooldownSender::pending_len`]). Additional receivers can be
+//! created with [`CooldownSender::subscribe`] - each of them gets its own channel and a copy of
+//! every batch. Use [`cooldown_buffer_shared`] to make those copies cheap.
+//!
+//! There is no timer to cancel and start again on every item. Receiving an item only moves the
+//! deadline of the next receive, so the cost per item stays low even at high item rates.
+//!
+//! Control operations of [`CooldownSender`] (like [`CooldownSender::flush`]) travel through the
+//! first channel together with the items, so they are handled in the same order they were sent.
+//!
+//! # Features
+//!
+//! - `ffi` - C bindings in the `ffi` module, build the crate as `cdylib` to use them from other
+//!   languages
+//! - `ipc` - `FrameWriter` and `forward_frames` for feeding the buffer from other processes
+//! - `serde` - implements `Serialize` and `Deserialize` for [`Checkpoint`]
 
+use ack::AckBatcher;
+use changed::Changed;
+use coalesce::Coalesce;
+use context::Accumulate;
+use dedup::Dedup;
 use doc_comment::doctest;
 use std::fmt::Debug;
-use std::sync::mpsc::{channel, Receiver, RecvError, Sender};
-use std::sync::{Arc, Mutex};
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
-use thread_timer::ThreadTimer;
+
+pub use ack::Acked;
+pub use cancel::CancellationToken;
+pub use checkpoint::Checkpoint;
+pub use collect::collect_until;
+pub use context::ContextBatch;
+pub use error::Error;
+pub use ext::IteratorExt;
+pub use fixed::FixedBatch;
+pub use guard::FlushGuard;
+pub use inline::InlineBuffer;
+#[cfg(feature = "ipc")]
+pub use ipc::{forward_frames, forward_frames_limited, FrameWriter, MAX_FRAME};
+pub use lines::{frames_buffer, lines_buffer};
+pub use notify::FlushWaiter;
+pub use outbox::Overflow;
+pub use receiver::{BatchReceiver, IntoIter, Iter};
+pub use retry::Retry;
+pub use router::Router;
+pub use scoped::cooldown_buffer_scoped;
+pub use sender::{CooldownSender, ItemSender, Message, Msg};
+pub use stats::{Histogram, Stats};
+pub use status::Status;
+pub use tagged::{cooldown_buffer_tagged, Tagged, TaggedSender};
+pub use wal::Wal;
+pub use watch::BatchWatch;
+pub use window::{sliding_window, tumbling_window};
+
+use buffer::Buffer;
+use worker::run;
+
+mod ack;
+mod buffer;
+mod cancel;
+mod changed;
+mod checkpoint;
+mod coalesce;
+mod collect;
+mod context;
+mod dedup;
+mod error;
+mod ext;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fixed;
+mod guard;
+mod inline;
+#[cfg(feature = "ipc")]
+mod ipc;
+mod lines;
+mod notify;
+mod outbox;
+mod rate;
+mod receiver;
+mod retry;
+mod router;
+mod scoped;
+mod sender;
+mod shared;
+mod stats;
+mod status;
+mod sync;
+mod tagged;
+mod wal;
+mod watch;
+mod watchdog;
+mod window;
+mod worker;
 
 doctest!("../README.md");
 
-/// Main and only function of this library. It starts the thread which receives the items, buffers
-/// them and controls the timer. Returns tuple `(Sender<T>, Receiver<Vec<T>>)`. You can send single
-/// item via `Sender<T>` and when the specified `cooldown_time` passes, you will get vector of
-/// buffered items back via `Receiver<Vec<T>>`.
+/// Batch emitted by [`cooldown_buffer_shared`]. Cloning it doesn't clone the items.
+pub type SharedBatch<T> = Arc<[T]>;
+
+/// Main function of this library. It starts the thread which receives the items, buffers them
+/// and counts the cooldown. Returns tuple `(CooldownSender<T>, BatchReceiver<T>)`. You can send
+/// single item via [`CooldownSender<T>`] and when the specified `cooldown_time` passes, you will
+/// get vector of buffered items back via [`BatchReceiver<T>`].
 ///
 /// # Arguments
 ///
-/// - `cooldown_time` - amount of time needed to "cool down" the receiving channel. After this time
-/// passes, the buffered items are sent through the `Receiver`
+/// - `channel` - channel used to pass items to the buffer thread, usually just
+///   [`channel()`](std::sync::mpsc::channel). Bounded
+///   [`sync_channel()`](std::sync::mpsc::sync_channel) works as well, see [`ItemSender`]
+/// - `cooldown_time` - amount of time needed to "cool down" the receiving channel. After this
+///   time passes, the buffered items are sent through the [`BatchReceiver`]. Zero disables the
+///   buffering - each item is emitted right away as a single-item batch and each batch sent with
+///   [`CooldownSender::send_batch`] as it is
 #[must_use]
 pub fn cooldown_buffer<T>(
-    (item_tx, item_rx): (Sender<T>, Receiver<T>),
+    channel: (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    cooldown_time: Duration,
+) -> (CooldownSender<T>, BatchReceiver<T>)
+where
+    T: 'static + Clone + Debug + Send,
+{
+    start(channel, cooldown_time, Buffer::new(|items| items))
+}
+
+/// Works like [`cooldown_buffer`], but creates the unbounded item channel itself.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use cooldown_buffer::cooldown_buffer_unbounded;
+///
+/// let (tx, rx) = cooldown_buffer_unbounded(Duration::from_millis(20));
+/// tx.send(1).unwrap();
+/// assert_eq!(rx.recv().unwrap(), vec![1]);
+/// ```
+#[must_use]
+pub fn cooldown_buffer_unbounded<T>(
+    cooldown_time: Duration,
+) -> (CooldownSender<T>, BatchReceiver<T>)
+where
+    T: 'static + Clone + Debug + Send,
+{
+    cooldown_buffer(channel(), cooldown_time)
+}
+
+/// Works like [`cooldown_buffer_unbounded`], but calls `on_flush` with every batch instead of
+/// returning the receiver. The callback runs on its own thread, so a slow callback doesn't stop
+/// the buffering. It's called for the last time after all the senders are dropped.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use cooldown_buffer::cooldown_buffer_with;
+/// use std::sync::mpsc::channel;
+///
+/// let (done_tx, done_rx) = channel();
+/// let tx = cooldown_buffer_with(Duration::from_millis(20), move |batch: Vec<u32>| {
+///     done_tx.send(batch.len()).unwrap();
+/// });
+/// tx.send_many(vec![1, 2]).unwrap();
+/// assert_eq!(done_rx.recv().unwrap(), 2);
+/// ```
+#[must_use]
+pub fn cooldown_buffer_with<T, F>(cooldown_time: Duration, on_flush: F) -> CooldownSender<T>
+where
+    T: 'static + Clone + Debug + Send,
+    F: 'static + FnMut(Vec<T>) + Send,
+{
+    let (sender, receiver) = cooldown_buffer_unbounded(cooldown_time);
+    thread::spawn(move || receiver.into_iter().for_each(on_flush));
+    sender
+}
+
+/// Works like [`cooldown_buffer_with`], but delivering the batch with `deliver` can fail. Failed
+/// delivery is retried according to `retry`. When all the attempts fail, the batch is sent to
+/// the [dead letters](CooldownSender::dead_letters), or dropped when the buffer is not running
+/// anymore.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use cooldown_buffer::{cooldown_buffer_with_retry, Retry};
+/// use std::sync::mpsc::channel;
+///
+/// let (done_tx, done_rx) = channel();
+/// let retry = Retry::new(3, Duration::from_millis(10));
+/// let tx = cooldown_buffer_with_retry(Duration::from_millis(20), retry, move |batch: &[u32]| {
+///     done_tx.send(batch.len())
+/// });
+/// tx.send_many(vec![1, 2]).unwrap();
+/// assert_eq!(done_rx.recv().unwrap(), 2);
+/// ```
+#[must_use]
+pub fn cooldown_buffer_with_retry<T, E, F>(
     cooldown_time: Duration,
-) -> (Sender<T>, Receiver<Vec<T>>)
+    retry: Retry,
+    mut deliver: F,
+) -> CooldownSender<T>
 where
     T: 'static + Clone + Debug + Send,
+    F: 'static + FnMut(&[T]) -> Result<(), E> + Send,
 {
-    let timer = ThreadTimer::new();
-    let items = Arc::new(Mutex::new(Vec::new()));
-    let (buffered_tx, buffered_rx) = channel::<Vec<T>>();
-
-    thread::spawn(move || -> Result<(), RecvError> {
-        loop {
-            let item = item_rx.recv()?;
-            // I don't care if the cancel failed. It can fail only if there is no running
-            // timer, which is fine from cancelling point of view - I just want
-            // to have not running timer.
-            let _ = timer.cancel();
-            items.lock().expect("poisoned mutex").push(item);
-
-            let cloned_items = items.clone();
-            let btx = buffered_tx.clone();
-
-            let _ = timer.start(cooldown_time, move || {
-                btx.send(cloned_items.lock().expect("poisoned mutex").clone())
-                    .expect("failed to send buffered items");
-                cloned_items.lock().expect("poisoned mutex").clear();
-            });
+    let (sender, receiver) = cooldown_buffer_unbounded(cooldown_time);
+    let discard = sender.discarder();
+    thread::spawn(move || {
+        for batch in receiver {
+            if !retry.run(|| deliver(&batch)) {
+                discard(batch);
+            }
         }
     });
+    sender
+}
+
+/// Works like [`cooldown_buffer`], but emits batches as `Arc<[T]>` instead of `Vec<T>`. Items are
+/// not cloned when the batch is delivered to many subscribers (see [`CooldownSender::subscribe`])
+/// or when it's kept around after receiving.
+#[must_use]
+pub fn cooldown_buffer_shared<T>(
+    channel: (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    cooldown_time: Duration,
+) -> (
+    CooldownSender<T, SharedBatch<T>>,
+    BatchReceiver<T, SharedBatch<T>>,
+)
+where
+    T: 'static + Clone + Debug + Send + Sync,
+{
+    start(channel, cooldown_time, Buffer::new(SharedBatch::from))
+}
+
+/// Works like [`cooldown_buffer`], but keeps only the most recent item. After the cooldown, this
+/// item alone is emitted, so each "batch" is just a single `T`. Useful when only the latest state
+/// matters, like the latest position of a cursor.
+#[must_use]
+pub fn cooldown_buffer_latest<T>(
+    channel: (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    cooldown_time: Duration,
+) -> (CooldownSender<T, T>, BatchReceiver<T, T>)
+where
+    T: 'static + Clone + Debug + Send,
+{
+    let buffer = Buffer::new(|mut items: Vec<T>| items.pop().expect("batch is never empty"));
+    start(channel, cooldown_time, buffer.coalesce(Coalesce::Latest))
+}
+
+/// Works like [`cooldown_buffer`], but keeps only the most recent item for each key returned by
+/// `key`. Newer item replaces the older one with the same key, keeping its position in the batch.
+/// Useful for file events, where only the latest state of each path matters.
+#[must_use]
+pub fn cooldown_buffer_keyed<T, K, F>(
+    channel: (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    cooldown_time: Duration,
+    key: F,
+) -> (CooldownSender<T>, BatchReceiver<T>)
+where
+    T: 'static + Clone + Debug + Send,
+    K: 'static + Eq + Hash + Send,
+    F: 'static + Fn(&T) -> K + Send,
+{
+    let buffer = Buffer::new(|items| items).coalesce(Coalesce::by_key(key));
+    start(channel, cooldown_time, buffer)
+}
+
+/// Works like [`cooldown_buffer`], but suppresses items with the same key returned by `key` as
+/// an item emitted within the last `window`, also across batches. Items with the same key in one
+/// batch are emitted only once too. Suppressed items don't extend the `window`. Useful for file
+/// events, when the same path keeps appearing in consecutive batches.
+///
+/// [Requeued](CooldownSender::requeue) items are never suppressed, they were not delivered
+/// after all. They still suppress later items with the same key.
+#[must_use]
+pub fn cooldown_buffer_dedup<T, K, F>(
+    channel: (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    cooldown_time: Duration,
+    window: Duration,
+    key: F,
+) -> (CooldownSender<T>, BatchReceiver<T>)
+where
+    T: 'static + Clone + Debug + Send,
+    K: 'static + Eq + Hash + Send,
+    F: 'static + Fn(&T) -> K + Send,
+{
+    let buffer = Buffer::new(|items| items).filtered(Dedup::new(window, key));
+    start(channel, cooldown_time, buffer)
+}
+
+/// Works like [`cooldown_buffer`], but doesn't emit the batch when `same` says it equals the
+/// previously emitted one. Useful when bursts of events keep bringing nothing new, e.g. while
+/// reloading the configuration.
+///
+/// # Example
+/// ```
+/// use std::sync::mpsc::channel;
+/// use std::time::Duration;
+/// use cooldown_buffer::cooldown_buffer_changed;
+///
+/// let (tx, rx) = cooldown_buffer_changed(channel(), Duration::from_millis(10), |a, b| a == b);
+/// tx.send("reload").unwrap();
+/// assert_eq!(rx.recv_timeout(Duration::from_millis(500)), Ok(vec!["reload"]));
+/// tx.send("reload").unwrap();
+/// assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+/// ```
+#[must_use]
+pub fn cooldown_buffer_changed<T, F>(
+    channel: (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    cooldown_time: Duration,
+    same: F,
+) -> (CooldownSender<T>, BatchReceiver<T>)
+where
+    T: 'static + Clone + Debug + Send,
+    F: 'static + Fn(&[T], &[T]) -> bool + Send,
+{
+    let buffer = Buffer::new(|items| items).filtered(Changed::new(same));
+    start(channel, cooldown_time, buffer)
+}
+
+/// Works like [`cooldown_buffer`], but each item extends the cooldown according to its `weight`:
+/// the cooldown lasts `cooldown_time * weight(item)` since the item was received, so heavier
+/// items keep the buffer open longer. Lighter items never shorten the cooldown started by the
+/// heavier ones, and items of weight zero don't extend it at all.
+#[must_use]
+pub fn cooldown_buffer_weighted<T, F>(
+    channel: (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    cooldown_time: Duration,
+    weight: F,
+) -> (CooldownSender<T>, BatchReceiver<T>)
+where
+    T: 'static + Clone + Debug + Send,
+    F: 'static + Fn(&T) -> u32 + Send,
+{
+    let buffer = Buffer::new(|items| items).weighted(weight);
+    start(channel, cooldown_time, buffer)
+}
+
+type ContextBuffer<T, C> = (
+    CooldownSender<T, ContextBatch<T, C>>,
+    BatchReceiver<T, ContextBatch<T, C>>,
+);
+
+/// Works like [`cooldown_buffer`], but accumulates the context from received items with
+/// `accumulate`, e.g. total size of items or their highest severity. The context is emitted
+/// together with the items, see [`ContextBatch`], and starts from `C::default()` again after each
+/// batch. When the batch is split (see [`CooldownSender::set_chunk_size`]), every chunk gets the
+/// context of all the items.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use cooldown_buffer::cooldown_buffer_with_context;
+/// use std::sync::mpsc::channel;
+///
+/// let (tx, rx) = cooldown_buffer_with_context(
+///     channel(),
+///     Duration::from_millis(20),
+///     |total: &mut usize, line: &String| *total += line.len(),
+/// );
+/// tx.send_many(vec!["ab".to_string(), "cde".to_string()]).unwrap();
+/// assert_eq!(rx.recv().unwrap().context, 5);
+/// ```
+#[must_use]
+pub fn cooldown_buffer_with_context<T, C, F>(
+    channel: (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    cooldown_time: Duration,
+    accumulate: F,
+) -> ContextBuffer<T, C>
+where
+    T: 'static + Clone + Debug + Send,
+    C: 'static + Clone + Debug + Default + Send,
+    F: 'static + Fn(&mut C, &T) + Send,
+{
+    let buffer = Buffer::with_batcher(Accumulate::new(accumulate));
+    start(channel, cooldown_time, buffer)
+}
 
-    (item_tx, buffered_rx)
+type AckedBuffer<T> = (CooldownSender<T, Acked<T>>, BatchReceiver<T, Acked<T>>);
+
+/// Works like [`cooldown_buffer`], but emits at most one batch at a time. The next batch is not
+/// emitted until the previous one is acknowledged with [`Acked::ack`], or all its copies are
+/// dropped. Items keep being buffered meanwhile and are emitted right after the acknowledgement
+/// when the buffer cooled down in the meantime. Pending items are emitted without waiting when
+/// the buffer stops.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use cooldown_buffer::cooldown_buffer_acked;
+/// use std::sync::mpsc::channel;
+///
+/// let (tx, rx) = cooldown_buffer_acked(channel(), Duration::from_millis(10));
+/// tx.send(1).unwrap();
+/// let batch = rx.recv().unwrap();
+/// tx.send(2).unwrap();
+/// assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+///
+/// batch.ack();
+/// assert_eq!(&*rx.recv().unwrap(), &[2]);
+/// ```
+#[must_use]
+pub fn cooldown_buffer_acked<T>(
+    channel: (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    cooldown_time: Duration,
+) -> AckedBuffer<T>
+where
+    T: 'static + Clone + Debug + Send,
+{
+    let batcher = AckBatcher::default();
+    let acker = batcher.acker();
+    let (sender, receiver) = start(
+        channel,
+        cooldown_time,
+        Buffer::with_batcher(batcher).gated(),
+    );
+    // Nothing can be emitted before the first item is sent, so the batches always get it.
+    let _ = acker.set(Arc::new(sender.acker()));
+    (sender, receiver)
+}
+
+type FixedBuffer<T, const N: usize> = (
+    CooldownSender<T, FixedBatch<T, N>>,
+    BatchReceiver<T, FixedBatch<T, N>>,
+);
+
+/// Works like [`cooldown_buffer`], but emits at most `N` items at once, stored inline in
+/// [`FixedBatch`]. Pending items are emitted as soon as there are `N` of them, without waiting for
+/// the cooldown. Receiving the batches doesn't allocate, and neither does sending the items when
+/// the bounded [`sync_channel()`](std::sync::mpsc::sync_channel) is used, so the buffer can be
+/// fed from threads which mustn't allocate. The buffer thread reserves room for `N` items once
+/// per batch, so buffering them never reallocates. Items sent together with
+/// [`CooldownSender::send_batch`] are emitted `N` at a time too, the rest stays pending.
+///
+/// # Panics
+///
+/// Panics when `N` is zero.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use cooldown_buffer::cooldown_buffer_fixed;
+/// use std::sync::mpsc::sync_channel;
+///
+/// let (tx, rx) = cooldown_buffer_fixed::<f32, 2>(sync_channel(64), Duration::from_secs(10));
+/// tx.send_many(vec![0.1, 0.2, 0.3]).unwrap();
+/// assert_eq!(&*rx.recv().unwrap(), &[0.1, 0.2]);
+/// ```
+#[must_use]
+pub fn cooldown_buffer_fixed<T, const N: usize>(
+    channel: (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    cooldown_time: Duration,
+) -> FixedBuffer<T, N>
+where
+    T: 'static + Copy + Debug + Default + Send,
+{
+    let capacity = NonZeroUsize::new(N).expect("N must be positive");
+    let buffer = Buffer::new(FixedBatch::from_items).with_capacity(capacity);
+    start(channel, cooldown_time, buffer)
+}
+
+/// Works like [`cooldown_buffer`], but keeps buffered items also in the write-ahead log, see
+/// [`Wal`]. Items recovered from the log are emitted as the first batch.
+#[must_use]
+pub fn cooldown_buffer_durable<T>(
+    channel: (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    cooldown_time: Duration,
+    wal: Wal<T>,
+) -> (CooldownSender<T>, BatchReceiver<T>)
+where
+    T: 'static + Clone + Debug + Send,
+{
+    let (sender, receiver) = start(
+        channel,
+        cooldown_time,
+        Buffer::new(|items| items).with_wal(wal),
+    );
+    // The buffer thread is running, so the flush can't fail.
+    let _ = sender.flush();
+    (sender, receiver)
+}
+
+fn start<T, B>(
+    (item_tx, item_rx): (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    cooldown_time: Duration,
+    buffer: Buffer<T, B>,
+) -> (CooldownSender<T, B>, BatchReceiver<T, B>)
+where
+    T: 'static + Clone + Debug + Send,
+    B: 'static + Clone + Send,
+{
+    let buffered_rx = buffer.shared().subscribe();
+    let sender = CooldownSender::new(item_tx, buffer.shared().clone());
+
+    thread::spawn(move || run(item_rx, buffer, cooldown_time));
+
+    (sender, BatchReceiver::new(buffered_rx))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::sync::mpsc::{channel, sync_channel};
 
     #[test]
     fn it_works() {
@@ -151,4 +598,357 @@ mod test {
         assert_eq!(buf3, vec![5, 6]);
         assert!(res.is_err());
     }
+
+    #[test]
+    fn callback_is_called_with_every_batch() {
+        // given
+        let (done_tx, done_rx) = channel();
+        let tx = cooldown_buffer_with(Duration::from_millis(20), move |batch| {
+            done_tx.send(batch).unwrap();
+        });
+
+        // when
+        tx.send_many(vec![1, 2]).unwrap();
+        let first = done_rx.recv_timeout(Duration::from_millis(500));
+        tx.send(3).unwrap();
+        drop(tx);
+        let second = done_rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(first, Ok(vec![1, 2]));
+        assert_eq!(second, Ok(vec![3]));
+    }
+
+    #[test]
+    fn zero_cooldown_emits_every_item_right_away() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::ZERO);
+
+        // when
+        for n in 1..=3 {
+            tx.send(n).unwrap();
+        }
+        tx.send_batch(vec![4, 5]).unwrap();
+        let batches: Vec<_> = (0..4).map(|_| rx.recv().unwrap()).collect();
+
+        // then
+        assert_eq!(batches, vec![vec![1], vec![2], vec![3], vec![4, 5]]);
+    }
+
+    #[test]
+    fn heavy_items_extend_the_cooldown() {
+        // given
+        let (tx, rx) = cooldown_buffer_weighted(channel(), Duration::from_millis(50), |n| *n);
+
+        // when
+        tx.send(4).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        tx.send(1).unwrap();
+        let early = rx.recv_timeout(Duration::from_millis(100));
+        let late = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(early, Err(Error::Timeout));
+        assert_eq!(late, Ok(vec![4, 1]));
+    }
+
+    #[test]
+    fn next_batch_waits_for_acknowledgement() {
+        // given
+        let (tx, rx) = cooldown_buffer_acked(channel(), Duration::from_millis(20));
+        tx.send(1).unwrap();
+        let first = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+
+        // when
+        tx.send(2).unwrap();
+        tx.send(3).unwrap();
+        let held = rx.recv_timeout(Duration::from_millis(200));
+        let flush_in = tx.time_until_flush();
+        first.ack();
+        let second = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+
+        // then
+        assert!(held.is_err());
+        assert_eq!(flush_in, None);
+        assert_eq!(&*second, &[2, 3]);
+    }
+
+    #[test]
+    fn batches_dropped_by_buffer_do_not_block_it() {
+        // given
+        let (tx, rx) = cooldown_buffer_acked(sync_channel(1), Duration::ZERO);
+        drop(rx);
+
+        // when
+        let producers: Vec<_> = (0..4)
+            .map(|_| {
+                let tx = tx.clone();
+                thread::spawn(move || (0..50).for_each(|n| tx.send(n).unwrap()))
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(500));
+
+        // then
+        assert!(producers.iter().all(thread::JoinHandle::is_finished));
+        assert_eq!(tx.pending_len(), 0);
+    }
+
+    #[test]
+    fn every_chunk_has_to_be_acknowledged() {
+        // given
+        let (tx, rx) = cooldown_buffer_acked(channel(), Duration::from_millis(20));
+        tx.set_chunk_size(NonZeroUsize::new(2)).unwrap();
+        tx.send_many(1..=5).unwrap();
+        let chunks: Vec<_> = (0..3)
+            .map(|_| rx.recv_timeout(Duration::from_millis(500)).unwrap())
+            .collect();
+
+        // when
+        tx.send(6).unwrap();
+        chunks[0].ack();
+        chunks[2].ack();
+        let held = rx.recv_timeout(Duration::from_millis(200));
+        chunks[1].ack();
+        let next = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+
+        // then
+        assert!(held.is_err());
+        assert_eq!(&*next, &[6]);
+    }
+
+    #[test]
+    fn dropped_batch_counts_as_acknowledged() {
+        // given
+        let (tx, rx) = cooldown_buffer_acked(channel(), Duration::from_millis(20));
+        tx.send(1).unwrap();
+
+        // when
+        drop(rx.recv_timeout(Duration::from_millis(500)).unwrap());
+        tx.send(2).unwrap();
+
+        // then
+        let second = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+        assert_eq!(&*second, &[2]);
+    }
+
+    #[test]
+    fn items_emitted_recently_are_suppressed() {
+        // given
+        let (tx, rx) = cooldown_buffer_dedup(
+            channel(),
+            Duration::from_millis(20),
+            Duration::from_secs(10),
+            |path: &&str| path.to_string(),
+        );
+        tx.send_many(vec!["a", "b"]).unwrap();
+        let first = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+
+        // when
+        tx.send_many(vec!["b", "c"]).unwrap();
+        let second = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+        tx.send("a").unwrap();
+        let third = rx.recv_timeout(Duration::from_millis(200));
+
+        // then
+        assert_eq!(first, vec!["a", "b"]);
+        assert_eq!(second, vec!["c"]);
+        assert_eq!(third, Err(Error::Timeout));
+        assert_eq!(tx.pending_len(), 0);
+    }
+
+    #[test]
+    fn requeued_items_are_not_suppressed() {
+        // given
+        let (tx, rx) = cooldown_buffer_dedup(
+            channel(),
+            Duration::from_millis(20),
+            Duration::from_secs(10),
+            |path: &&str| path.to_string(),
+        );
+        tx.send_many(vec!["a", "b"]).unwrap();
+        let first = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+
+        // when
+        tx.requeue(first).unwrap();
+        tx.send_many(vec!["b", "c"]).unwrap();
+        let second = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+
+        // then
+        assert_eq!(second, vec!["a", "b", "c"]);
+        assert_eq!(tx.pending_len(), 0);
+    }
+
+    #[test]
+    fn unchanged_batches_are_not_emitted() {
+        // given
+        let (tx, rx) = cooldown_buffer_changed(channel(), Duration::from_millis(20), |a, b| {
+            a.len() == b.len()
+        });
+        tx.send_many(vec![1, 2]).unwrap();
+        let first = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+
+        // when
+        tx.send_many(vec![3, 4]).unwrap();
+        let unchanged = rx.recv_timeout(Duration::from_millis(200));
+        tx.send(5).unwrap();
+        let changed = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(first, vec![1, 2]);
+        assert_eq!(unchanged, Err(Error::Timeout));
+        assert_eq!(changed, Ok(vec![5]));
+        assert_eq!(tx.pending_len(), 0);
+    }
+
+    #[test]
+    fn fixed_buffer_emits_when_full() {
+        // given
+        let (tx, rx) = cooldown_buffer_fixed::<u32, 2>(channel(), Duration::from_secs(10));
+
+        // when
+        (1..=5).for_each(|n| tx.send(n).unwrap());
+        let first = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+        let second = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+
+        // then
+        assert_eq!(&*first, &[1, 2]);
+        assert_eq!(&*second, &[3, 4]);
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        assert_eq!(tx.pending_len(), 1);
+    }
+
+    #[test]
+    fn fixed_buffer_splits_big_batches() {
+        // given
+        let (tx, rx) = cooldown_buffer_fixed::<u32, 2>(channel(), Duration::from_secs(10));
+
+        // when
+        tx.set_chunk_size(NonZeroUsize::new(10)).unwrap();
+        tx.send_many(1..=5).unwrap();
+        let first = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+        let second = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+
+        // then
+        assert_eq!(&*first, &[1, 2]);
+        assert_eq!(&*second, &[3, 4]);
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        assert_eq!(tx.snapshot(), vec![5]);
+    }
+
+    #[test]
+    fn failed_delivery_is_retried() {
+        // given
+        let (done_tx, done_rx) = channel();
+        let mut attempts = 0;
+        let retry = Retry::new(3, Duration::from_millis(10));
+
+        // when
+        let tx = cooldown_buffer_with_retry(Duration::from_millis(20), retry, move |batch| {
+            attempts += 1;
+            if attempts < 3 {
+                return Err("unavailable");
+            }
+            done_tx.send((attempts, batch.to_vec())).unwrap();
+            Ok(())
+        });
+        tx.send(1).unwrap();
+
+        // then
+        assert_eq!(
+            done_rx.recv_timeout(Duration::from_millis(500)),
+            Ok((3, vec![1]))
+        );
+    }
+
+    #[test]
+    fn undelivered_batch_goes_to_dead_letters() {
+        // given
+        let retry = Retry::new(1, Duration::from_millis(10));
+        let tx = cooldown_buffer_with_retry(Duration::from_millis(20), retry, |_: &[u32]| {
+            Err("unavailable")
+        });
+        let dead_letters = tx.dead_letters();
+
+        // when
+        tx.send_many(vec![1, 2]).unwrap();
+
+        // then
+        assert_eq!(
+            dead_letters.recv_timeout(Duration::from_millis(500)),
+            Ok(vec![1, 2])
+        );
+    }
+
+    #[test]
+    fn context_is_accumulated_per_batch() {
+        // given
+        let (tx, rx) = cooldown_buffer_with_context(
+            channel(),
+            Duration::from_millis(20),
+            |max: &mut u32, n: &u32| *max = (*max).max(*n),
+        );
+
+        // when
+        tx.send_many(vec![3, 7, 5]).unwrap();
+        let first = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+        tx.send(2).unwrap();
+        let second = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+
+        // then
+        assert_eq!(first.items, vec![3, 7, 5]);
+        assert_eq!(first.context, 7);
+        assert_eq!(second.context, 2);
+    }
+
+    #[test]
+    fn shared_batches_point_to_the_same_items() {
+        // given
+        let (tx, rx1) = cooldown_buffer_shared(channel(), Duration::from_millis(20));
+        let rx2 = tx.subscribe();
+
+        // when
+        tx.send_many(vec![1, 2, 3]).unwrap();
+        let buf1 = rx1.recv_timeout(Duration::from_millis(500)).unwrap();
+        let buf2 = rx2.recv_timeout(Duration::from_millis(500)).unwrap();
+
+        // then
+        assert_eq!(*buf1, [1, 2, 3]);
+        assert!(Arc::ptr_eq(&buf1, &buf2));
+    }
+
+    #[test]
+    fn latest_mode_emits_only_the_most_recent_item() {
+        // given
+        let (tx, rx) = cooldown_buffer_latest(channel(), Duration::from_millis(50));
+
+        // when
+        tx.send(1).unwrap();
+        tx.send_batch(vec![2, 3]).unwrap();
+        tx.send(4).unwrap();
+        let latest = rx.recv_timeout(Duration::from_millis(500));
+        let next = rx.recv_timeout(Duration::from_millis(100));
+
+        // then
+        assert_eq!(latest, Ok(4));
+        assert_eq!(next, Err(Error::Timeout));
+    }
+
+    #[test]
+    fn keyed_mode_keeps_latest_item_per_key() {
+        // given
+        let (tx, rx) =
+            cooldown_buffer_keyed(channel(), Duration::from_millis(50), |e: &(&str, u32)| e.0);
+
+        // when
+        tx.send(("a.txt", 1)).unwrap();
+        tx.send(("b.txt", 1)).unwrap();
+        tx.send_batch(vec![("a.txt", 2), ("c.txt", 1)]).unwrap();
+        let first = rx.recv_timeout(Duration::from_millis(500));
+        tx.send(("a.txt", 3)).unwrap();
+        let second = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(first, Ok(vec![("a.txt", 2), ("b.txt", 1), ("c.txt", 1)]));
+        assert_eq!(second, Ok(vec![("a.txt", 3)]));
+    }
 }
@@ -0,0 +1,144 @@
+use crate::cooldown_buffer_unbounded;
+use crate::receiver::BatchReceiver;
+use std::io::{self, BufRead};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Reads lines from `reader` on a background thread and batches them like
+/// [`cooldown_buffer`](crate::cooldown_buffer) does. Useful for bursts of lines, e.g. when
+/// following a log file.
+///
+/// Line endings are stripped. Reading stops at the end of the stream or at the first error, then
+/// pending lines are emitted right away and the receiver gets disconnected. The returned thread
+/// ends with that error, e.g. [`ErrorKind::InvalidData`](io::ErrorKind::InvalidData) for a line
+/// which is not UTF-8. To follow a growing file, pass a reader which blocks waiting for more data
+/// instead of reporting the end.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use cooldown_buffer::lines_buffer;
+/// use std::io::Cursor;
+///
+/// let (rx, reading) = lines_buffer(Cursor::new("first\nsecond\n"), Duration::from_millis(20));
+/// assert_eq!(rx.recv().unwrap(), vec!["first", "second"]);
+/// assert!(reading.join().unwrap().is_ok());
+/// ```
+#[must_use]
+pub fn lines_buffer<R>(
+    reader: R,
+    cooldown_time: Duration,
+) -> (BatchReceiver<String>, JoinHandle<io::Result<()>>)
+where
+    R: 'static + BufRead + Send,
+{
+    let (tx, rx) = cooldown_buffer_unbounded(cooldown_time);
+    let reading = thread::spawn(move || {
+        for line in reader.lines() {
+            if tx.send(line?).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    });
+    (rx, reading)
+}
+
+/// Works like [`lines_buffer`], but splits the stream at `delimiter` instead of line endings.
+/// Frames are emitted as bytes, without the delimiter.
+#[must_use]
+pub fn frames_buffer<R>(
+    reader: R,
+    delimiter: u8,
+    cooldown_time: Duration,
+) -> (BatchReceiver<Vec<u8>>, JoinHandle<io::Result<()>>)
+where
+    R: 'static + BufRead + Send,
+{
+    let (tx, rx) = cooldown_buffer_unbounded(cooldown_time);
+    let reading = thread::spawn(move || {
+        for frame in reader.split(delimiter) {
+            let mut frame = frame?;
+            if frame.last() == Some(&delimiter) {
+                frame.pop();
+            }
+            if tx.send(frame).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    });
+    (rx, reading)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{self, Cursor, Read};
+    use std::sync::mpsc::{channel, Receiver};
+
+    /// Reader returning chunks as they arrive through the channel, like a followed file.
+    struct Chunks(Receiver<&'static [u8]>, &'static [u8]);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.1.is_empty() {
+                self.1 = self.0.recv().unwrap_or_default();
+            }
+            let len = self.1.len().min(buf.len());
+            buf[..len].copy_from_slice(&self.1[..len]);
+            self.1 = &self.1[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn bursts_of_lines_are_batched() {
+        // given
+        let (chunks_tx, chunks_rx) = channel();
+        let reader = io::BufReader::new(Chunks(chunks_rx, &[]));
+        let (rx, _reading) = lines_buffer(reader, Duration::from_millis(50));
+
+        // when
+        chunks_tx.send(b"a\nb\r\n".as_slice()).unwrap();
+        let first = rx.recv_timeout(Duration::from_millis(500));
+        chunks_tx.send(b"c\n".as_slice()).unwrap();
+        let second = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(first, Ok(vec!["a".to_string(), "b".to_string()]));
+        assert_eq!(second, Ok(vec!["c".to_string()]));
+    }
+
+    #[test]
+    fn frames_are_split_at_delimiter() {
+        // given
+        let reader = Cursor::new(b"a\0bc\0d".to_vec());
+
+        // when
+        let (rx, reading) = frames_buffer(reader, 0, Duration::from_millis(20));
+
+        // then
+        assert_eq!(
+            rx.recv(),
+            Ok(vec![b"a".to_vec(), b"bc".to_vec(), b"d".to_vec()])
+        );
+        assert!(reading.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn invalid_utf8_line_is_reported() {
+        // given
+        let reader = Cursor::new(b"a\n\xff\nb\n".to_vec());
+
+        // when
+        let (rx, reading) = lines_buffer(reader, Duration::from_millis(20));
+
+        // then
+        assert_eq!(rx.recv(), Ok(vec!["a".to_string()]));
+        assert_eq!(
+            reading.join().unwrap().map_err(|e| e.kind()),
+            Err(io::ErrorKind::InvalidData)
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use crate::error::Error;
+use crate::sync::lock;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Counts emitted batches and wakes up [`FlushWaiter`]s.
+#[derive(Debug, Default)]
+pub(crate) struct FlushSignal {
+    state: Mutex<Flushes>,
+    changed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Flushes {
+    emitted: u64,
+    closed: bool,
+}
+
+impl FlushSignal {
+    pub(crate) fn notify(&self) {
+        lock(&self.state).emitted += 1;
+        self.changed.notify_all();
+    }
+
+    pub(crate) fn close(&self) {
+        lock(&self.state).closed = true;
+        self.changed.notify_all();
+    }
+}
+
+/// Handle waiting for emitted batches without receiving them, created with
+/// [`CooldownSender::flush_waiter`](crate::CooldownSender::flush_waiter). Useful when some part of
+/// the application only needs to know that something was emitted.
+///
+/// Batches emitted between the waits are not missed, the next wait returns right away then.
+#[derive(Debug, Clone)]
+pub struct FlushWaiter {
+    signal: Arc<FlushSignal>,
+    seen: u64,
+}
+
+impl FlushWaiter {
+    pub(crate) fn new(signal: Arc<FlushSignal>) -> Self {
+        let seen = lock(&signal.state).emitted;
+        Self { signal, seen }
+    }
+
+    /// Blocks until a batch is emitted and returns the number of batches emitted since the last
+    /// wait, or since the waiter was created.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer stopped and nothing more was emitted.
+    pub fn wait(&mut self) -> Result<u64, Error> {
+        self.wait_until(None)
+    }
+
+    /// Works like [`FlushWaiter::wait`], but blocks no longer than `timeout`. Timeout too big to
+    /// be represented, like `Duration::MAX`, doesn't limit the wait.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] when no batch was emitted within `timeout` and
+    /// [`Error::Disconnected`] when the buffer stopped and nothing more was emitted.
+    pub fn wait_timeout(&mut self, timeout: Duration) -> Result<u64, Error> {
+        self.wait_until(Instant::now().checked_add(timeout))
+    }
+
+    fn wait_until(&mut self, deadline: Option<Instant>) -> Result<u64, Error> {
+        let mut state = lock(&self.signal.state);
+        loop {
+            if state.emitted > self.seen {
+                let emitted = state.emitted - self.seen;
+                self.seen = state.emitted;
+                return Ok(emitted);
+            }
+            if state.closed {
+                return Err(Error::Disconnected);
+            }
+            state = match deadline {
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        return Err(Error::Timeout);
+                    }
+                    self.signal
+                        .changed
+                        .wait_timeout(state, left)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+                None => self
+                    .signal
+                    .changed
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{cooldown_buffer, Error};
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    #[test]
+    fn waiter_is_woken_up_by_emitted_batches() {
+        // given
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_millis(10));
+        let mut waiter = tx.flush_waiter();
+        let nothing = waiter.wait_timeout(Duration::from_millis(50));
+
+        // when
+        tx.send(1).unwrap();
+        let flushed = waiter.wait_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(nothing, Err(Error::Timeout));
+        assert_eq!(flushed, Ok(1));
+    }
+
+    #[test]
+    fn huge_timeout_waits_like_wait() {
+        // given
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_millis(10));
+        let mut waiter = tx.flush_waiter();
+
+        // when
+        tx.send(1).unwrap();
+        let flushed = waiter.wait_timeout(Duration::MAX);
+
+        // then
+        assert_eq!(flushed, Ok(1));
+    }
+
+    #[test]
+    fn waiter_is_disconnected_when_buffer_stops() {
+        // given
+        let (tx, _rx) = cooldown_buffer::<u32>(channel(), Duration::from_millis(10));
+        let mut waiter = tx.flush_waiter();
+
+        // when
+        drop(tx);
+
+        // then
+        assert_eq!(waiter.wait(), Err(Error::Disconnected));
+    }
+}
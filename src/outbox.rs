@@ -0,0 +1,427 @@
+use crate::error::Error;
+use crate::sync::lock;
+use std::collections::VecDeque;
+use std::fmt::{self, Debug};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// What happens with the emitted batch when the receiver created with
+/// [`CooldownSender::subscribe_bounded`](crate::CooldownSender::subscribe_bounded) already has
+/// the maximum number of undelivered batches.
+pub enum Overflow<B> {
+    /// The buffer thread waits until the receiver takes the oldest batch. Other receivers don't
+    /// get the batch until then either.
+    Block,
+
+    /// The oldest undelivered batch is dropped to make room for the new one. Its items are sent
+    /// to the [dead letters](crate::CooldownSender::dead_letters).
+    DropOldest,
+
+    /// The new batch is merged into the newest undelivered one with the given function, e.g.
+    /// `|older: &mut Vec<u32>, newer| older.extend(newer)`.
+    Merge(fn(&mut B, B)),
+}
+
+impl<B> Clone for Overflow<B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<B> Copy for Overflow<B> {}
+
+impl<B> Debug for Overflow<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Block => write!(f, "Block"),
+            Self::DropOldest => write!(f, "DropOldest"),
+            Self::Merge(_) => write!(f, "Merge"),
+        }
+    }
+}
+
+/// Creates queue of batches holding at most `capacity` of them, see [`Overflow`].
+pub(crate) fn outbox<B>(
+    capacity: NonZeroUsize,
+    overflow: Overflow<B>,
+) -> (OutboxSender<B>, OutboxReceiver<B>) {
+    queue(
+        VecDeque::with_capacity(capacity.get()),
+        capacity.get(),
+        overflow,
+    )
+}
+
+/// Creates queue of batches without any limit.
+pub(crate) fn unbounded<B>() -> (OutboxSender<B>, OutboxReceiver<B>) {
+    queue(VecDeque::new(), usize::MAX, Overflow::Block)
+}
+
+fn queue<B>(
+    batches: VecDeque<B>,
+    capacity: usize,
+    overflow: Overflow<B>,
+) -> (OutboxSender<B>, OutboxReceiver<B>) {
+    let queue = Arc::new(Queue {
+        state: Mutex::new(State {
+            batches,
+            capacity,
+            overflow,
+            sender: true,
+            receivers: 1,
+        }),
+        changed: Condvar::new(),
+    });
+    (
+        OutboxSender {
+            queue: queue.clone(),
+            dropped: None,
+        },
+        OutboxReceiver { queue },
+    )
+}
+
+struct Queue<B> {
+    state: Mutex<State<B>>,
+    /// Notified whenever anything in the state changes.
+    changed: Condvar,
+}
+
+struct State<B> {
+    batches: VecDeque<B>,
+    capacity: usize,
+    overflow: Overflow<B>,
+    /// Whether the sender is still connected.
+    sender: bool,
+    /// Number of connected receivers, they share the batches.
+    receivers: usize,
+}
+
+impl<B> Queue<B> {
+    fn wait<'a>(&self, state: MutexGuard<'a, State<B>>) -> MutexGuard<'a, State<B>> {
+        self.changed
+            .wait(state)
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Waits like [`Queue::wait`], but not past the `deadline`. Returns `None` once it passed.
+    fn wait_until<'a>(
+        &self,
+        state: MutexGuard<'a, State<B>>,
+        deadline: Option<Instant>,
+    ) -> Option<MutexGuard<'a, State<B>>> {
+        let Some(deadline) = deadline else {
+            return Some(self.wait(state));
+        };
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return None;
+        }
+        let (state, _) = self
+            .changed
+            .wait_timeout(state, left)
+            .unwrap_or_else(PoisonError::into_inner);
+        Some(state)
+    }
+}
+
+/// Batch given back by [`OutboxSender::send_until`].
+pub(crate) enum Unsent<B> {
+    /// The receiver is gone.
+    Disconnected(B),
+    /// The receiver didn't make room for the batch before the deadline.
+    TimedOut(B),
+}
+
+pub(crate) struct OutboxSender<B> {
+    queue: Arc<Queue<B>>,
+    /// Gets batches dropped with [`Overflow::DropOldest`].
+    dropped: Option<Box<dyn Fn(B) + Send>>,
+}
+
+impl<B> OutboxSender<B> {
+    /// Passes batches dropped to make room for the new ones to `dropped`.
+    pub(crate) fn on_dropped(mut self, dropped: impl Fn(B) + Send + 'static) -> Self {
+        self.dropped = Some(Box::new(dropped));
+        self
+    }
+
+    /// Queues the batch according to the [`Overflow`] policy. Gives the batch back when the
+    /// receiver is gone.
+    pub(crate) fn send(&self, batch: B) -> Result<(), B> {
+        self.send_until(batch, None).map_err(|unsent| match unsent {
+            Unsent::Disconnected(batch) | Unsent::TimedOut(batch) => batch,
+        })
+    }
+
+    /// Sends like [`OutboxSender::send`], but with [`Overflow::Block`] it waits for room only
+    /// until the `deadline`.
+    pub(crate) fn send_until(&self, batch: B, deadline: Option<Instant>) -> Result<(), Unsent<B>> {
+        let mut oldest = None;
+        let mut state = lock(&self.queue.state);
+        loop {
+            if state.receivers == 0 {
+                return Err(Unsent::Disconnected(batch));
+            }
+            if state.batches.len() < state.capacity {
+                state.batches.push_back(batch);
+                break;
+            }
+            match state.overflow {
+                Overflow::Block => match self.queue.wait_until(state, deadline) {
+                    Some(waited) => state = waited,
+                    None => return Err(Unsent::TimedOut(batch)),
+                },
+                Overflow::DropOldest => {
+                    oldest = state.batches.pop_front();
+                    state.batches.push_back(batch);
+                    break;
+                }
+                Overflow::Merge(merge) => {
+                    if let Some(newest) = state.batches.back_mut() {
+                        merge(newest, batch);
+                    }
+                    break;
+                }
+            }
+        }
+        drop(state);
+        self.queue.changed.notify_all();
+        if let (Some(oldest), Some(dropped)) = (oldest, &self.dropped) {
+            dropped(oldest);
+        }
+        Ok(())
+    }
+}
+
+impl<B> Drop for OutboxSender<B> {
+    fn drop(&mut self) {
+        lock(&self.queue.state).sender = false;
+        self.queue.changed.notify_all();
+    }
+}
+
+impl<B> Debug for OutboxSender<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutboxSender").finish_non_exhaustive()
+    }
+}
+
+pub(crate) struct OutboxReceiver<B> {
+    queue: Arc<Queue<B>>,
+}
+
+impl<B> OutboxReceiver<B> {
+    pub(crate) fn recv(&self) -> Result<B, Error> {
+        self.recv_until(None)
+    }
+
+    pub(crate) fn recv_timeout(&self, timeout: Duration) -> Result<B, Error> {
+        // Timeout too big to be represented is as good as no timeout at all.
+        self.recv_until(Instant::now().checked_add(timeout))
+    }
+
+    pub(crate) fn try_recv(&self) -> Result<B, Error> {
+        let mut state = lock(&self.queue.state);
+        let batch = state.batches.pop_front();
+        drop(state);
+        match batch {
+            Some(batch) => {
+                self.queue.changed.notify_all();
+                Ok(batch)
+            }
+            None if self.is_disconnected() => Err(Error::Disconnected),
+            None => Err(Error::Empty),
+        }
+    }
+
+    fn recv_until(&self, deadline: Option<Instant>) -> Result<B, Error> {
+        let mut state = lock(&self.queue.state);
+        loop {
+            if let Some(batch) = state.batches.pop_front() {
+                drop(state);
+                self.queue.changed.notify_all();
+                return Ok(batch);
+            }
+            if !state.sender {
+                return Err(Error::Disconnected);
+            }
+            state = match self.queue.wait_until(state, deadline) {
+                Some(state) => state,
+                None => return Err(Error::Timeout),
+            };
+        }
+    }
+
+    fn is_disconnected(&self) -> bool {
+        !lock(&self.queue.state).sender
+    }
+}
+
+impl<B> Clone for OutboxReceiver<B> {
+    fn clone(&self) -> Self {
+        lock(&self.queue.state).receivers += 1;
+        Self {
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<B> Drop for OutboxReceiver<B> {
+    fn drop(&mut self) {
+        let mut state = lock(&self.queue.state);
+        state.receivers -= 1;
+        if state.receivers == 0 {
+            state.batches.clear();
+            drop(state);
+            self.queue.changed.notify_all();
+        }
+    }
+}
+
+impl<B> Debug for OutboxReceiver<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutboxReceiver").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    fn capacity(capacity: usize) -> NonZeroUsize {
+        NonZeroUsize::new(capacity).unwrap()
+    }
+
+    #[test]
+    fn oldest_batch_is_dropped_when_full() {
+        // given
+        let (tx, rx) = outbox(capacity(2), Overflow::DropOldest);
+
+        // when
+        (1..=3).for_each(|batch| tx.send(vec![batch]).unwrap());
+
+        // then
+        assert_eq!(rx.try_recv(), Ok(vec![2]));
+        assert_eq!(rx.try_recv(), Ok(vec![3]));
+        assert_eq!(rx.try_recv(), Err(Error::Empty));
+    }
+
+    #[test]
+    fn blocked_send_gives_up_at_deadline() {
+        // given
+        let (tx, _rx) = outbox(capacity(1), Overflow::Block);
+        tx.send(1).unwrap();
+
+        // when
+        let unsent = tx.send_until(2, Some(Instant::now() + Duration::from_millis(50)));
+
+        // then
+        assert!(matches!(unsent, Err(Unsent::TimedOut(2))));
+    }
+
+    #[test]
+    fn huge_timeout_waits_like_recv() {
+        // given
+        let (tx, rx) = outbox::<u32>(capacity(1), Overflow::Block);
+
+        // when
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            tx.send(1).unwrap();
+        });
+        let batch = rx.recv_timeout(Duration::MAX);
+        handle.join().unwrap();
+
+        // then
+        assert_eq!(batch, Ok(1));
+        assert_eq!(rx.recv_timeout(Duration::MAX), Err(Error::Disconnected));
+    }
+
+    #[test]
+    fn dropped_batch_is_passed_on() {
+        // given
+        let (dropped_tx, dropped_rx) = std::sync::mpsc::channel();
+        let (tx, rx) = outbox(capacity(1), Overflow::DropOldest);
+        let tx = tx.on_dropped(move |batch| dropped_tx.send(batch).unwrap());
+
+        // when
+        (1..=3).for_each(|batch| tx.send(batch).unwrap());
+
+        // then
+        assert_eq!(rx.try_recv(), Ok(3));
+        assert_eq!(dropped_rx.try_iter().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn new_batch_is_merged_when_full() {
+        // given
+        let (tx, rx) = outbox(
+            capacity(1),
+            Overflow::Merge(|older: &mut Vec<u32>, newer| older.extend(newer)),
+        );
+
+        // when
+        tx.send(vec![1]).unwrap();
+        tx.send(vec![2, 3]).unwrap();
+
+        // then
+        assert_eq!(rx.try_recv(), Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn sender_blocks_until_batch_is_taken() {
+        // given
+        let (tx, rx) = outbox(capacity(1), Overflow::Block);
+        tx.send(1).unwrap();
+
+        // when
+        let handle = thread::spawn(move || tx.send(2));
+        thread::sleep(Duration::from_millis(50));
+        let blocked = !handle.is_finished();
+        let first = rx.recv();
+
+        // then
+        assert!(blocked);
+        assert_eq!(first, Ok(1));
+        assert_eq!(handle.join().unwrap(), Ok(()));
+        assert_eq!(rx.recv(), Ok(2));
+        assert_eq!(rx.recv(), Err(Error::Disconnected));
+    }
+
+    #[test]
+    fn cloned_receivers_share_batches() {
+        // given
+        let (tx, rx1) = unbounded();
+        let rx2 = rx1.clone();
+
+        // when
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        drop(rx1);
+        tx.send(3).unwrap();
+
+        // then
+        assert_eq!(rx2.try_recv(), Ok(1));
+        assert_eq!(rx2.try_recv(), Ok(2));
+        assert_eq!(rx2.try_recv(), Ok(3));
+        assert_eq!(rx2.try_recv(), Err(Error::Empty));
+    }
+
+    #[test]
+    fn blocked_sender_is_released_when_receiver_is_dropped() {
+        // given
+        let (tx, rx) = outbox(capacity(1), Overflow::Block);
+        tx.send(1).unwrap();
+
+        // when
+        let handle = thread::spawn(move || tx.send(2));
+        thread::sleep(Duration::from_millis(50));
+        drop(rx);
+
+        // then
+        assert_eq!(handle.join().unwrap(), Err(2));
+    }
+}
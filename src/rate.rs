@@ -0,0 +1,76 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Token bucket allowing at most `capacity` acquisitions per `period`, refilled continuously.
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(capacity: u32, period: Duration) -> Self {
+        assert!(capacity > 0, "rate limit capacity must be positive");
+        assert!(!period.is_zero(), "rate limit period must be positive");
+        let capacity = f64::from(capacity);
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: capacity / period.as_secs_f64(),
+            refilled_at: Instant::now(),
+        }
+    }
+
+    /// Takes one token, sleeping until it's available.
+    pub(crate) fn acquire(&mut self) {
+        self.refill();
+        if self.tokens < 1.0 {
+            let missing = 1.0 - self.tokens;
+            thread::sleep(Duration::from_secs_f64(missing / self.refill_per_sec));
+            self.refill();
+        }
+        self.tokens = (self.tokens - 1.0).max(0.0);
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.refilled_at = now;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bucket_allows_burst_up_to_capacity() {
+        // given
+        let mut bucket = TokenBucket::new(2, Duration::from_secs(10));
+        let start = Instant::now();
+
+        // when
+        bucket.acquire();
+        bucket.acquire();
+
+        // then
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn bucket_waits_for_next_token() {
+        // given
+        let mut bucket = TokenBucket::new(1, Duration::from_millis(100));
+        bucket.acquire();
+        let start = Instant::now();
+
+        // when
+        bucket.acquire();
+
+        // then
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+}
@@ -0,0 +1,452 @@
+use crate::buffer::into_chunks;
+use crate::cooldown_buffer;
+use crate::error::Error;
+use crate::outbox::{self, OutboxReceiver, OutboxSender};
+use crate::rate::TokenBucket;
+use std::fmt::Debug;
+use std::iter;
+use std::marker::PhantomData;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Receiving half of the cooldown buffer returned by [`cooldown_buffer`](crate::cooldown_buffer).
+///
+/// Each received value is one batch - items buffered until the buffer cooled down. By default
+/// batch is a `Vec<T>`, buffers created with
+/// [`cooldown_buffer_shared`](crate::cooldown_buffer_shared) emit `Arc<[T]>` and buffers created
+/// with [`cooldown_buffer_latest`](crate::cooldown_buffer_latest) emit single `T` instead.
+///
+/// The receiver can be cloned to spread the work among many threads. Clones share the batches,
+/// so each batch is received by only one of them. Use
+/// [`CooldownSender::subscribe`](crate::CooldownSender::subscribe) to get a copy of every batch
+/// instead.
+#[derive(Debug)]
+pub struct BatchReceiver<T, B = Vec<T>> {
+    rx: OutboxReceiver<B>,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T, B> BatchReceiver<T, B> {
+    pub(crate) fn new(rx: OutboxReceiver<B>) -> Self {
+        Self {
+            rx,
+            _item: PhantomData,
+        }
+    }
+
+    /// Blocks until the next batch is emitted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when no more batches can ever be emitted.
+    pub fn recv(&self) -> Result<B, Error> {
+        self.rx.recv()
+    }
+
+    /// Blocks until the next batch is emitted, but no longer than `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] when no batch was emitted within `timeout` and
+    /// [`Error::Disconnected`] when no more batches can ever be emitted.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<B, Error> {
+        self.rx.recv_timeout(timeout)
+    }
+
+    /// Returns already emitted batch without blocking.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Empty`] when there is no batch waiting and [`Error::Disconnected`] when
+    /// no more batches can ever be emitted.
+    pub fn try_recv(&self) -> Result<B, Error> {
+        self.rx.try_recv()
+    }
+
+    /// Returns an iterator blocking on each batch. It ends when the buffer is disconnected.
+    pub fn iter(&self) -> Iter<'_, B> {
+        Iter { rx: &self.rx }
+    }
+}
+
+impl<T, B> BatchReceiver<T, B>
+where
+    T: 'static,
+    B: 'static + Clone + Debug + Send,
+{
+    /// Feeds batches of this receiver into next cooldown buffer, which groups them into batches of
+    /// batches. Useful for multi-level coalescing, e.g. items grouped per second and then those
+    /// groups grouped per minute.
+    ///
+    /// The next buffer is running as long as this receiver is connected.
+    #[must_use]
+    pub fn rebuffer(self, cooldown_time: Duration) -> BatchReceiver<B> {
+        let (tx, rx) = cooldown_buffer(channel(), cooldown_time);
+        thread::spawn(move || {
+            for batch in self {
+                if tx.send(batch).is_err() {
+                    break;
+                }
+            }
+        });
+        rx
+    }
+
+    /// Limits the rate of batches to at most `max_batches` per `period`. Batches emitted faster
+    /// are queued and released as soon as the limit allows. Up to `max_batches` queued batches
+    /// are released at once after a quiet period.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `max_batches` or `period` is zero.
+    #[must_use]
+    pub fn rate_limited(self, max_batches: u32, period: Duration) -> BatchReceiver<T, B> {
+        let mut bucket = TokenBucket::new(max_batches, period);
+        let (tx, rx) = outbox::unbounded();
+        thread::spawn(move || {
+            for batch in self {
+                bucket.acquire();
+                if tx.send(batch).is_err() {
+                    break;
+                }
+            }
+        });
+        BatchReceiver::new(rx)
+    }
+
+    /// Feeds items of every batch of this receiver into next cooldown buffer. It works like
+    /// [`BatchReceiver::rebuffer`], but the batches are flattened, so the next buffer emits plain
+    /// items again.
+    #[must_use]
+    pub fn rebuffer_flat(self, cooldown_time: Duration) -> BatchReceiver<T>
+    where
+        T: Clone + Debug + Send,
+        B: IntoIterator<Item = T>,
+    {
+        let (tx, rx) = cooldown_buffer(channel(), cooldown_time);
+        thread::spawn(move || {
+            for batch in self {
+                if tx.send_many(batch).is_err() {
+                    break;
+                }
+            }
+        });
+        rx
+    }
+}
+
+impl<T> BatchReceiver<T>
+where
+    T: 'static + Send,
+{
+    /// Maps items of every batch with `map`, spread across `threads` threads. Each batch is split
+    /// into `threads` parts mapped at the same time, the order of items is kept. Useful for
+    /// expensive processing of big batches, e.g. hashing files. The threads are started once and
+    /// map parts of all the batches.
+    ///
+    /// When `map` panics, the returned receiver gets disconnected.
+    #[must_use]
+    pub fn map_parallel<R, F>(self, threads: NonZeroUsize, map: F) -> BatchReceiver<R>
+    where
+        R: 'static + Send,
+        F: 'static + Fn(T) -> R + Send + Sync,
+    {
+        let (tx, rx) = outbox::unbounded();
+        let (job_tx, job_rx) = outbox::unbounded();
+        let (part_tx, part_rx) = channel();
+        let map = Arc::new(map);
+        for _ in 0..threads.get() {
+            let (jobs, parts, map) = (job_rx.clone(), part_tx.clone(), map.clone());
+            thread::spawn(move || map_parts(&jobs, &parts, &*map));
+        }
+        // Workers stop once the jobs sender is dropped with this thread.
+        thread::spawn(move || {
+            for batch in self {
+                let Some(mapped) = map_batch(batch, threads, &job_tx, &part_rx) else {
+                    break;
+                };
+                if tx.send(mapped).is_err() {
+                    break;
+                }
+            }
+        });
+        BatchReceiver::new(rx)
+    }
+}
+
+/// Part of the batch with its position in the batch.
+type Job<T> = (usize, Vec<T>);
+
+/// Mapped part of the batch with its position, or the panic of `map`.
+type Part<R> = (usize, thread::Result<Vec<R>>);
+
+/// Splits items into parts mapped by the workers and joins them back in order. Returns `None`
+/// when `map` panicked.
+fn map_batch<T, R>(
+    items: Vec<T>,
+    threads: NonZeroUsize,
+    jobs: &OutboxSender<Job<T>>,
+    parts: &Receiver<Part<R>>,
+) -> Option<Vec<R>> {
+    let chunk_size = items.len().div_ceil(threads.get()).max(1);
+    let chunks = into_chunks(items, chunk_size);
+    let count = chunks.len();
+    for job in chunks.into_iter().enumerate() {
+        jobs.send(job).ok()?;
+    }
+    let mut mapped: Vec<Option<Vec<R>>> = iter::repeat_with(|| None).take(count).collect();
+    for _ in 0..count {
+        let (index, part) = parts.recv().ok()?;
+        mapped[index] = Some(part.ok()?);
+    }
+    Some(mapped.into_iter().flatten().flatten().collect())
+}
+
+/// Maps parts of batches until there are no more jobs.
+fn map_parts<T, R, F>(jobs: &OutboxReceiver<Job<T>>, parts: &Sender<Part<R>>, map: &F)
+where
+    F: Fn(T) -> R,
+{
+    while let Ok((index, items)) = jobs.recv() {
+        let part = panic::catch_unwind(AssertUnwindSafe(|| items.into_iter().map(map).collect()));
+        if parts.send((index, part)).is_err() {
+            break;
+        }
+    }
+}
+
+impl<T, B> Clone for BatchReceiver<T, B> {
+    fn clone(&self) -> Self {
+        Self::new(self.rx.clone())
+    }
+}
+
+/// Blocking iterator over batches borrowed from [`BatchReceiver`].
+#[derive(Debug)]
+pub struct Iter<'a, B> {
+    rx: &'a OutboxReceiver<B>,
+}
+
+impl<B> Iterator for Iter<'_, B> {
+    type Item = B;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rx.recv().ok()
+    }
+}
+
+/// Blocking iterator over batches taking ownership of [`BatchReceiver`].
+#[derive(Debug)]
+pub struct IntoIter<B> {
+    rx: OutboxReceiver<B>,
+}
+
+impl<B> Iterator for IntoIter<B> {
+    type Item = B;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<T, B> IntoIterator for BatchReceiver<T, B> {
+    type Item = B;
+    type IntoIter = IntoIter<B>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { rx: self.rx }
+    }
+}
+
+impl<'a, T, B> IntoIterator for &'a BatchReceiver<T, B> {
+    type Item = B;
+    type IntoIter = Iter<'a, B>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{cooldown_buffer, Error};
+    use std::collections::HashSet;
+    use std::num::NonZeroUsize;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn recv_timeout_gives_up_when_nothing_is_emitted() {
+        // given
+        let (_tx, rx) = cooldown_buffer::<u32>(channel(), Duration::from_millis(10));
+
+        // when
+        let res = rx.recv_timeout(Duration::from_millis(50));
+
+        // then
+        assert_eq!(res, Err(Error::Timeout));
+    }
+
+    #[test]
+    fn try_recv_reports_empty_buffer() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+
+        // when
+        tx.send(1).unwrap();
+        let res = rx.try_recv();
+
+        // then
+        assert_eq!(res, Err(Error::Empty));
+    }
+
+    #[test]
+    fn cloned_receivers_share_the_work() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        let workers: Vec<_> = (0..3)
+            .map(|_| {
+                let rx = rx.clone();
+                thread::spawn(move || rx.into_iter().flatten().collect::<Vec<u32>>())
+            })
+            .collect();
+        drop(rx);
+
+        // when
+        for n in 0..30 {
+            tx.send(n).unwrap();
+            tx.flush().unwrap();
+        }
+        drop(tx);
+        let mut received: Vec<_> = workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect();
+        received.sort_unstable();
+
+        // then
+        assert_eq!(received, (0..30).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn batches_are_mapped_in_parallel_keeping_order() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+        let threads = NonZeroUsize::new(3).unwrap();
+
+        // when
+        let rx = rx.map_parallel(threads, |n: u32| n * 10);
+        tx.send_many(1..=7).unwrap();
+
+        // then
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(500)),
+            Ok(vec![10, 20, 30, 40, 50, 60, 70])
+        );
+    }
+
+    #[test]
+    fn batches_are_mapped_by_the_same_threads() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+        let threads = NonZeroUsize::new(2).unwrap();
+        let rx = rx.map_parallel(threads, |_: u32| thread::current().id());
+
+        // when
+        let mut ids = HashSet::new();
+        for _ in 0..3 {
+            tx.send_many(1..=4).unwrap();
+            ids.extend(rx.recv_timeout(Duration::from_millis(500)).unwrap());
+        }
+
+        // then
+        assert!(ids.len() <= 2);
+    }
+
+    #[test]
+    fn panicking_map_disconnects_receiver() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+        let threads = NonZeroUsize::new(2).unwrap();
+        let rx = rx.map_parallel(threads, |n: u32| if n == 3 { panic!("boom") } else { n });
+
+        // when
+        tx.send_many(1..=4).unwrap();
+
+        // then
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(500)),
+            Err(Error::Disconnected)
+        );
+    }
+
+    #[test]
+    fn iterator_ends_when_buffer_is_disconnected() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+
+        // when
+        tx.send(1).unwrap();
+        drop(tx);
+        let batches: Vec<Vec<u32>> = rx.into_iter().collect();
+
+        // then
+        assert_eq!(batches, vec![vec![1]]);
+    }
+
+    #[test]
+    fn rebuffer_groups_batches() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+        let rx = rx.rebuffer(Duration::from_millis(150));
+
+        // when
+        tx.send_many(vec![1, 2]).unwrap();
+        thread::sleep(Duration::from_millis(60)); // first level cooled down
+        tx.send(3).unwrap();
+        let buf = rx.recv_timeout(Duration::from_millis(1000));
+
+        // then
+        assert_eq!(buf, Ok(vec![vec![1, 2], vec![3]]));
+    }
+
+    #[test]
+    fn rebuffer_flat_joins_batches() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+        let rx = rx.rebuffer_flat(Duration::from_millis(150));
+
+        // when
+        tx.send_many(vec![1, 2]).unwrap();
+        thread::sleep(Duration::from_millis(60)); // first level cooled down
+        tx.send(3).unwrap();
+        let buf = rx.recv_timeout(Duration::from_millis(1000));
+
+        // then
+        assert_eq!(buf, Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn rate_limited_receiver_spreads_batches() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        let rx = rx.rate_limited(1, Duration::from_millis(100));
+
+        // when
+        for n in 1..=3 {
+            tx.send(n).unwrap();
+            tx.flush().unwrap();
+        }
+        let start = Instant::now();
+        let batches: Vec<_> = (0..3).map(|_| rx.recv().unwrap()).collect();
+
+        // then
+        assert_eq!(batches, vec![vec![1], vec![2], vec![3]]);
+        assert!(start.elapsed() >= Duration::from_millis(180));
+    }
+}
@@ -0,0 +1,89 @@
+use std::thread;
+use std::time::Duration;
+
+/// How failed delivery of the batch is retried, see
+/// [`cooldown_buffer_with_retry`](crate::cooldown_buffer_with_retry). The wait between attempts
+/// starts from `backoff` and doubles after each attempt, up to the maximum backoff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    retries: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Retry {
+    /// Retries the delivery at most `retries` times, first after `backoff`. The backoff is not
+    /// limited.
+    #[must_use]
+    pub fn new(retries: u32, backoff: Duration) -> Self {
+        Self {
+            retries,
+            backoff,
+            max_backoff: Duration::MAX,
+        }
+    }
+
+    /// Limits the wait between attempts to `max_backoff`.
+    #[must_use]
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Calls `attempt` until it succeeds or there are no retries left. Returns whether it
+    /// succeeded.
+    pub(crate) fn run<E>(&self, mut attempt: impl FnMut() -> Result<(), E>) -> bool {
+        let mut backoff = self.backoff.min(self.max_backoff);
+        for _ in 0..self.retries {
+            if attempt().is_ok() {
+                return true;
+            }
+            thread::sleep(backoff);
+            backoff = backoff.saturating_mul(2).min(self.max_backoff);
+        }
+        attempt().is_ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn attempts_are_retried_until_success() {
+        // given
+        let retry = Retry::new(3, Duration::from_millis(1));
+        let mut attempts = 0;
+
+        // when
+        let delivered = retry.run(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(())
+            } else {
+                Ok(())
+            }
+        });
+
+        // then
+        assert!(delivered);
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn retry_gives_up_after_all_retries() {
+        // given
+        let retry = Retry::new(2, Duration::from_millis(1)).max_backoff(Duration::from_millis(2));
+        let mut attempts = 0;
+
+        // when
+        let delivered = retry.run(|| {
+            attempts += 1;
+            Err::<(), _>(())
+        });
+
+        // then
+        assert!(!delivered);
+        assert_eq!(attempts, 3);
+    }
+}
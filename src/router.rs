@@ -0,0 +1,157 @@
+use crate::cooldown_buffer;
+use crate::error::Error;
+use crate::receiver::BatchReceiver;
+use crate::sender::CooldownSender;
+use crate::sync::lock;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::hash::Hash;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Splits items between many cooldown buffers, one per route.
+///
+/// Route of each item is decided by the classification closure. Each route is buffered and
+/// cooled down independently of others. Routes are created with [`Router::route`], which also
+/// returns the receiver of batches of that route.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use cooldown_buffer::Router;
+/// use anyhow::Result;
+///
+/// fn main() -> Result<()> {
+///     let router = Router::new(Duration::from_millis(50), |n: &u32| n % 2 == 0);
+///     let even = router.route(true);
+///     let odd = router.route(false);
+///
+///     for n in 1..=4 {
+///         router.send(n)?;
+///     }
+///
+///     assert_eq!(even.recv()?, vec![2, 4]);
+///     assert_eq!(odd.recv()?, vec![1, 3]);
+///
+///     Ok(())
+/// }
+/// ```
+pub struct Router<T, K> {
+    classify: Arc<dyn Fn(&T) -> K + Send + Sync>,
+    routes: Arc<Mutex<HashMap<K, CooldownSender<T>>>>,
+    cooldown_time: Duration,
+}
+
+impl<T, K> Router<T, K>
+where
+    T: 'static + Clone + Debug + Send,
+    K: Eq + Hash,
+{
+    /// Creates router without any routes. Every route created later cools down after
+    /// `cooldown_time`.
+    pub fn new<F>(cooldown_time: Duration, classify: F) -> Self
+    where
+        F: 'static + Fn(&T) -> K + Send + Sync,
+    {
+        Self {
+            classify: Arc::new(classify),
+            routes: Arc::new(Mutex::new(HashMap::new())),
+            cooldown_time,
+        }
+    }
+
+    /// Returns receiver of batches for route `key`. The route is created if it doesn't exist yet,
+    /// otherwise new subscriber is added to it (see [`CooldownSender::subscribe`]).
+    pub fn route(&self, key: K) -> BatchReceiver<T> {
+        let mut routes = lock(&self.routes);
+        if let Some(tx) = routes.get(&key) {
+            return tx.subscribe();
+        }
+        let (tx, rx) = cooldown_buffer(channel(), self.cooldown_time);
+        routes.insert(key, tx);
+        rx
+    }
+
+    /// Sends the item to the buffer of its route.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoRoute`] when the route of the item wasn't created and
+    /// [`Error::Disconnected`] when the buffer of the route is not running anymore.
+    pub fn send(&self, item: T) -> Result<(), Error> {
+        let key = (self.classify)(&item);
+        let routes = lock(&self.routes);
+        routes.get(&key).ok_or(Error::NoRoute)?.send(item)
+    }
+
+    /// Emits buffered items of every route right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer of any route is not running anymore.
+    pub fn flush(&self) -> Result<(), Error> {
+        let routes = lock(&self.routes);
+        routes.values().try_for_each(CooldownSender::flush)
+    }
+}
+
+impl<T, K> Clone for Router<T, K> {
+    fn clone(&self) -> Self {
+        Self {
+            classify: self.classify.clone(),
+            routes: self.routes.clone(),
+            cooldown_time: self.cooldown_time,
+        }
+    }
+}
+
+impl<T, K> Debug for Router<T, K>
+where
+    T: Debug,
+    K: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Router")
+            .field("routes", &self.routes)
+            .field("cooldown_time", &self.cooldown_time)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn routes_are_buffered_independently() {
+        // given
+        let router = Router::new(Duration::from_millis(50), |s: &&str| s.len());
+        let short = router.route(1);
+        let long = router.route(3);
+
+        // when
+        router.send("a").unwrap();
+        router.send("abc").unwrap();
+        router.send("b").unwrap();
+        let short_buf = short.recv_timeout(Duration::from_millis(500));
+        let long_buf = long.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(short_buf, Ok(vec!["a", "b"]));
+        assert_eq!(long_buf, Ok(vec!["abc"]));
+    }
+
+    #[test]
+    fn sending_to_unknown_route_fails() {
+        // given
+        let router = Router::new(Duration::from_millis(50), |n: &u32| *n);
+        let _rx = router.route(1);
+
+        // when
+        let res = router.send(2);
+
+        // then
+        assert_eq!(res, Err(Error::NoRoute));
+    }
+}
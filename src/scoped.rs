@@ -0,0 +1,93 @@
+use crate::buffer::Buffer;
+use crate::receiver::BatchReceiver;
+use crate::sender::{CooldownSender, ItemSender, Message};
+use crate::worker::run;
+use std::fmt::Debug;
+use std::sync::mpsc::Receiver;
+use std::thread::Scope;
+use std::time::Duration;
+
+/// Works like [`cooldown_buffer`](crate::cooldown_buffer), but the buffer thread is spawned in
+/// the `scope` (see [`std::thread::scope`]), so items can borrow data from the caller.
+///
+/// The buffer stops when all the senders are dropped, emitting pending items right away. Because
+/// the scope waits for its threads, all the senders have to be dropped before the scope ends.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use cooldown_buffer::cooldown_buffer_scoped;
+/// use std::path::PathBuf;
+/// use std::sync::mpsc::channel;
+/// use std::thread;
+///
+/// let paths = vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")];
+///
+/// thread::scope(|s| {
+///     let (tx, rx) = cooldown_buffer_scoped(s, channel(), Duration::from_millis(50));
+///     for path in &paths {
+///         tx.send(path.as_path()).unwrap();
+///     }
+///     drop(tx);
+///
+///     assert_eq!(rx.recv().unwrap().len(), 2);
+/// });
+/// ```
+pub fn cooldown_buffer_scoped<'scope, 'env, T>(
+    scope: &'scope Scope<'scope, 'env>,
+    (item_tx, item_rx): (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    cooldown_time: Duration,
+) -> (CooldownSender<T>, BatchReceiver<T>)
+where
+    T: 'scope + Clone + Debug + Send,
+{
+    let buffer = Buffer::new(|items| items);
+    let buffered_rx = buffer.shared().subscribe();
+    let sender = CooldownSender::new(item_tx, buffer.shared().clone());
+
+    scope.spawn(move || run(item_rx, buffer, cooldown_time));
+
+    (sender, BatchReceiver::new(buffered_rx))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    #[test]
+    fn items_can_borrow_from_caller() {
+        // given
+        let words = [String::from("a"), String::from("b")];
+
+        thread::scope(|s| {
+            let (tx, rx) = cooldown_buffer_scoped(s, channel(), Duration::from_millis(20));
+
+            // when
+            tx.send_many(words.iter().map(String::as_str)).unwrap();
+            let buf = rx.recv_timeout(Duration::from_millis(500));
+
+            // then
+            assert_eq!(buf, Ok(vec!["a", "b"]));
+        });
+    }
+
+    #[test]
+    fn dropping_senders_emits_pending_items_and_ends_scope() {
+        // given
+        let word = String::from("a");
+
+        thread::scope(|s| {
+            let (tx, rx) = cooldown_buffer_scoped(s, channel(), Duration::from_secs(10));
+
+            // when
+            tx.send(word.as_str()).unwrap();
+            drop(tx);
+            let batches: Vec<_> = rx.into_iter().collect();
+
+            // then
+            assert_eq!(batches, vec![vec!["a"]]);
+        });
+    }
+}
@@ -0,0 +1,1169 @@
+use crate::cancel::CancellationToken;
+use crate::checkpoint::Checkpoint;
+use crate::error::Error;
+use crate::guard::FlushGuard;
+use crate::notify::FlushWaiter;
+use crate::outbox::{self, OutboxReceiver, OutboxSender, Overflow};
+use crate::receiver::BatchReceiver;
+use crate::shared::Shared;
+use crate::stats::Stats;
+use crate::status::Status;
+use crate::watch::BatchWatch;
+use crate::watchdog;
+use std::io;
+use std::num::NonZeroUsize;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{channel, RecvTimeoutError, SendError, Sender, SyncSender};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+/// Message travelling through the item channel of the buffer.
+///
+/// It is opaque on purpose. Items and control operations (like [`CooldownSender::flush`]) share
+/// the same channel, so they are always handled in the order they were sent. Messages are created
+/// by [`CooldownSender`] or converted from [`Msg`], otherwise you only need to name this type when
+/// creating the channel.
+#[derive(Debug)]
+pub struct Message<T>(pub(crate) Command<T>);
+
+/// Control envelope for the code holding only the sending half of the item channel. Converted
+/// into the [`Message`], it can be sent straight through the channel, so items and control
+/// operations stay in order without passing [`CooldownSender`] around.
+///
+/// # Example
+/// ```
+/// use std::sync::mpsc::channel;
+/// use std::time::Duration;
+/// use cooldown_buffer::{cooldown_buffer, Msg};
+///
+/// let (tx, rx) = channel();
+/// let (_sender, receiver) = cooldown_buffer((tx.clone(), rx), Duration::from_secs(60));
+/// tx.send(Msg::Item(1).into()).unwrap();
+/// tx.send(Msg::Flush.into()).unwrap();
+/// assert_eq!(receiver.recv_timeout(Duration::from_millis(500)), Ok(vec![1]));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Msg<T> {
+    /// Buffers the item, see [`CooldownSender::send`].
+    Item(T),
+
+    /// Emits buffered items right away, see [`CooldownSender::flush`].
+    Flush,
+
+    /// Holds buffered items until resumed, see [`CooldownSender::pause`].
+    Pause,
+
+    /// Starts emitting buffered items again, see [`CooldownSender::resume`].
+    Resume,
+
+    /// Changes the cooldown time, see [`CooldownSender::set_cooldown`].
+    SetCooldown(Duration),
+}
+
+impl<T> From<Msg<T>> for Message<T> {
+    fn from(msg: Msg<T>) -> Self {
+        Self(match msg {
+            Msg::Item(item) => Command::Item(item),
+            Msg::Flush => Command::Flush,
+            Msg::Pause => Command::Pause,
+            Msg::Resume => Command::Resume,
+            Msg::SetCooldown(time) => Command::SetCooldown(time),
+        })
+    }
+}
+
+/// Sending half of the item channel, created from [`Sender`] or [`SyncSender`]. It allows passing
+/// both [`channel`](std::sync::mpsc::channel) and [`sync_channel`](std::sync::mpsc::sync_channel)
+/// to the buffer constructors. With the latter, sending blocks while the channel is full.
+#[derive(Debug)]
+pub struct ItemSender<T>(Tx<T>);
+
+#[derive(Debug)]
+enum Tx<T> {
+    Unbounded(Sender<Message<T>>),
+    Bounded(SyncSender<Message<T>>),
+}
+
+impl<T> ItemSender<T> {
+    fn send(&self, message: Message<T>) -> Result<(), SendError<Message<T>>> {
+        match &self.0 {
+            Tx::Unbounded(tx) => tx.send(message),
+            Tx::Bounded(tx) => tx.send(message),
+        }
+    }
+
+    /// Sends the message unless the bounded channel is full.
+    fn try_send(&self, message: Message<T>) {
+        let _ = match &self.0 {
+            Tx::Unbounded(tx) => tx.send(message).map_err(|_| ()),
+            Tx::Bounded(tx) => tx.try_send(message).map_err(|_| ()),
+        };
+    }
+}
+
+impl<T> From<Sender<Message<T>>> for ItemSender<T> {
+    fn from(tx: Sender<Message<T>>) -> Self {
+        Self(Tx::Unbounded(tx))
+    }
+}
+
+impl<T> From<SyncSender<Message<T>>> for ItemSender<T> {
+    fn from(tx: SyncSender<Message<T>>) -> Self {
+        Self(Tx::Bounded(tx))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum Command<T> {
+    Item(T),
+    Batch(Vec<T>),
+    Requeue(Vec<T>),
+    Flush,
+    Clear,
+    Snapshot(Sender<Vec<T>>),
+    Drain(Sender<Vec<T>>),
+    DeadLetters(OutboxSender<Vec<T>>),
+    /// Items of batches dropped by the lagging receiver, see [`Overflow::DropOldest`].
+    WatchDropped(OutboxReceiver<Vec<T>>),
+    Discard(Vec<T>),
+    Ack,
+    Pause,
+    Resume,
+    SetCooldown(Duration),
+    SetBurstCooldown(usize, Duration),
+    AlignFlushes(Option<Duration>),
+    SetChunkSize(Option<NonZeroUsize>),
+    Shutdown,
+    /// Without the deadline, pending items are always emitted.
+    ShutdownBy(Option<Instant>, Sender<usize>),
+}
+
+/// Sending half of the cooldown buffer returned by [`cooldown_buffer`](crate::cooldown_buffer).
+///
+/// Besides sending items, it allows controlling the buffer. It can be cloned to send items from
+/// many threads, all clones feed the same buffer.
+#[derive(Debug)]
+pub struct CooldownSender<T, B = Vec<T>> {
+    tx: Arc<ItemSender<T>>,
+    shared: Arc<Shared<B>>,
+}
+
+impl<T, B> CooldownSender<T, B> {
+    pub(crate) fn new(tx: impl Into<ItemSender<T>>, shared: Arc<Shared<B>>) -> Self {
+        Self {
+            tx: Arc::new(tx.into()),
+            shared,
+        }
+    }
+
+    /// Sends single item to the buffer and restarts the cooldown.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    pub fn send(&self, item: T) -> Result<(), Error> {
+        self.command(Command::Item(item))
+    }
+
+    /// Sends all the items to the buffer at once. It works like [`CooldownSender::send_batch`],
+    /// but accepts any iterator.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    pub fn send_many<I>(&self, items: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.send_batch(items.into_iter().collect())
+    }
+
+    /// Sends all the items to the buffer at once. They are counted as a single activity, so the
+    /// cooldown is restarted only once, no matter how many items there are. Sending empty batch
+    /// doesn't restart the cooldown.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    pub fn send_batch(&self, items: Vec<T>) -> Result<(), Error> {
+        self.command(Command::Batch(items))
+    }
+
+    /// Puts previously emitted items back to the buffer, e.g. when delivering them failed, so they
+    /// are emitted again with the next batch. Requeued items go before the pending ones and
+    /// restart the cooldown like [`CooldownSender::send_batch`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    pub fn requeue(&self, items: Vec<T>) -> Result<(), Error> {
+        self.command(Command::Requeue(items))
+    }
+
+    /// Emits buffered items right away, without waiting for the cooldown. Nothing is emitted when
+    /// the buffer is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    pub fn flush(&self) -> Result<(), Error> {
+        self.command(Command::Flush)
+    }
+
+    /// Stops emitting buffered items when the buffer cools down. Items are still buffered and
+    /// emitted after [`CooldownSender::resume`]. Explicit flushes still emit them, as well as
+    /// full [`cooldown_buffer_fixed`](crate::cooldown_buffer_fixed). It has no effect for the
+    /// windows.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    pub fn pause(&self) -> Result<(), Error> {
+        self.command(Command::Pause)
+    }
+
+    /// Starts the cooldown of items buffered while paused, see [`CooldownSender::pause`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    pub fn resume(&self) -> Result<(), Error> {
+        self.command(Command::Resume)
+    }
+
+    /// Drops buffered items without emitting them and stops the cooldown. Useful when the
+    /// operation producing the items was cancelled and the partial batch is not needed anymore.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    pub fn clear(&self) -> Result<(), Error> {
+        self.command(Command::Clear)
+    }
+
+    /// Creates receiver of items discarded without being emitted, e.g. by
+    /// [`CooldownSender::clear`]. Each value holds items discarded at once. Items discarded before
+    /// the receiver is created are not delivered to it.
+    #[must_use]
+    pub fn dead_letters(&self) -> BatchReceiver<T> {
+        let (tx, rx) = outbox::unbounded();
+        // When the buffer is not running, the receiver is just disconnected.
+        let _ = self.command(Command::DeadLetters(tx));
+        BatchReceiver::new(rx)
+    }
+
+    /// Returns a function sending items to the dead letters. It doesn't keep the buffer running,
+    /// items discarded after the buffer stopped are lost.
+    pub(crate) fn discarder(&self) -> impl Fn(Vec<T>) + Send
+    where
+        T: 'static + Send,
+    {
+        let tx = Arc::downgrade(&self.tx);
+        move |items| {
+            if let Some(tx) = Weak::upgrade(&tx) {
+                let _ = tx.send(Message(Command::Discard(items)));
+            }
+        }
+    }
+
+    /// Returns a function telling the buffer that the emitted batch was acknowledged. It doesn't
+    /// keep the buffer running.
+    ///
+    /// The acknowledgement is signalled with the flag, batches can be dropped on the buffer thread
+    /// and it must not wait for room in its own channel. The message only wakes the thread up, when
+    /// the channel is full it wakes up anyway.
+    pub(crate) fn acker(&self) -> impl Fn() + Send + Sync
+    where
+        T: 'static + Send,
+    {
+        let tx = Arc::downgrade(&self.tx);
+        let acked = self.shared.acked().clone();
+        move || {
+            acked.store(true, Ordering::Relaxed);
+            if let Some(tx) = Weak::upgrade(&tx) {
+                tx.try_send(Message(Command::Ack));
+            }
+        }
+    }
+
+    /// Returns a guard which flushes the buffer when dropped, see [`FlushGuard`].
+    #[must_use]
+    pub fn flush_guard(&self) -> FlushGuard<T, B> {
+        FlushGuard::new(self.clone())
+    }
+
+    /// Returns the number of items buffered and not yet emitted. Items which are still in the
+    /// channel, waiting to be picked up by the buffer thread, are not counted.
+    #[must_use]
+    pub fn pending_len(&self) -> usize {
+        self.shared.pending_len()
+    }
+
+    /// Returns the number of pending items together with the estimated time until they are
+    /// emitted. Useful for health checks, to see whether the buffer is backing up.
+    #[must_use]
+    pub fn status(&self) -> Status {
+        Status {
+            pending: self.shared.pending_len(),
+            flush_in: self.shared.flush_in(),
+        }
+    }
+
+    /// Returns how much of the current cooldown remains, `None` when nothing is waiting to be
+    /// emitted. Useful for showing a countdown until the next batch.
+    #[must_use]
+    pub fn time_until_flush(&self) -> Option<Duration> {
+        self.shared.flush_in()
+    }
+
+    /// Returns distributions of item latency and batch size observed since the buffer was
+    /// created. Useful for picking the cooldown time for the workload.
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        self.shared.stats()
+    }
+
+    /// Returns the last error of writing the log of
+    /// [`cooldown_buffer_durable`](crate::cooldown_buffer_durable), `None` when there was none
+    /// since the previous call. Items which failed to be written are still emitted, but they
+    /// won't be recovered after a crash.
+    #[must_use]
+    pub fn take_wal_error(&self) -> Option<io::Error> {
+        self.shared.take_wal_error()
+    }
+
+    /// Changes the cooldown time. The new value is used starting from the next received item.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    pub fn set_cooldown(&self, cooldown_time: Duration) -> Result<(), Error> {
+        self.command(Command::SetCooldown(cooldown_time))
+    }
+
+    /// Uses `cooldown_time` instead of the regular cooldown while more than `threshold` items are
+    /// pending. With longer burst cooldown, isolated items are emitted quickly, while bursts are
+    /// coalesced into bigger batches. `usize::MAX` as `threshold` turns it off. Like
+    /// [`CooldownSender::set_cooldown`], it's used starting from the next received item.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    pub fn set_burst_cooldown(
+        &self,
+        threshold: usize,
+        cooldown_time: Duration,
+    ) -> Result<(), Error> {
+        self.command(Command::SetBurstCooldown(threshold, cooldown_time))
+    }
+
+    /// Emits pending items also at every multiple of `period` since the UNIX epoch, e.g. at each
+    /// full minute for `period` of 60 seconds, even when the buffer didn't cool down yet. `None`
+    /// turns it off. Like [`CooldownSender::set_cooldown`], it has no effect for the windows,
+    /// see [`tumbling_window`](crate::tumbling_window) for batching by wall-clock time alone.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `period` is zero.
+    pub fn align_flushes(&self, period: Option<Duration>) -> Result<(), Error> {
+        assert!(
+            period.is_none_or(|period| !period.is_zero()),
+            "period must be positive"
+        );
+        self.command(Command::AlignFlushes(period))
+    }
+
+    /// Limits the number of items in emitted batches. Pending items which don't fit are emitted
+    /// as many consecutive batches of at most `chunk_size` items. `None` removes the limit.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    pub fn set_chunk_size(&self, chunk_size: Option<NonZeroUsize>) -> Result<(), Error> {
+        self.command(Command::SetChunkSize(chunk_size))
+    }
+
+    /// Returns a copy of items buffered and not yet emitted. The buffer is not affected. It works
+    /// like [`CooldownSender::snapshot`].
+    #[must_use]
+    pub fn checkpoint(&self) -> Checkpoint<T>
+    where
+        T: Clone,
+    {
+        Checkpoint::new(self.snapshot())
+    }
+
+    /// Returns a copy of items buffered and not yet emitted, e.g. to preview them while the buffer
+    /// is still cooling down. Neither the buffer nor the cooldown is affected.
+    ///
+    /// The copy is made by the buffer thread, so it includes every item sent before. It blocks
+    /// until the buffer thread gets to it and returns no items when the buffer is not running.
+    #[must_use]
+    pub fn snapshot(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let (reply_tx, reply_rx) = channel();
+        if self.command(Command::Snapshot(reply_tx)).is_err() {
+            return Vec::new();
+        }
+        reply_rx.recv().unwrap_or_default()
+    }
+
+    /// Removes items buffered and not yet emitted and returns them, stopping the cooldown. Unlike
+    /// [`CooldownSender::flush`], the items are handed to the caller instead of the receivers.
+    ///
+    /// Like [`CooldownSender::snapshot`], it includes every item sent before and blocks until the
+    /// buffer thread gets to it. Returns no items when the buffer is not running.
+    #[must_use]
+    pub fn drain(&self) -> Vec<T> {
+        let (reply_tx, reply_rx) = channel();
+        if self.command(Command::Drain(reply_tx)).is_err() {
+            return Vec::new();
+        }
+        reply_rx.recv().unwrap_or_default()
+    }
+
+    /// Puts items from the checkpoint back into the buffer, as with
+    /// [`CooldownSender::send_batch`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    pub fn restore(&self, checkpoint: Checkpoint<T>) -> Result<(), Error> {
+        self.send_batch(checkpoint.into_items())
+    }
+
+    /// Calls `on_stuck` from a separate thread when items are pending for longer than `threshold`
+    /// without being emitted, e.g. because the buffer thread died or the cooldown keeps being
+    /// restarted. It's called once per such episode, with the time the items are pending. The
+    /// watchdog stops when the buffer and all its handles are dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics when `threshold` is zero.
+    pub fn watchdog<F>(&self, threshold: Duration, on_stuck: F)
+    where
+        B: 'static + Send,
+        F: 'static + FnMut(Duration) + Send,
+    {
+        assert!(!threshold.is_zero(), "threshold must be positive");
+        watchdog::spawn(Arc::downgrade(&self.shared), threshold, on_stuck);
+    }
+
+    /// Creates new receiver of batches. Every receiver gets its own copy of each batch emitted
+    /// after the subscription. Batches emitted before are not delivered to it.
+    #[must_use]
+    pub fn subscribe(&self) -> BatchReceiver<T, B> {
+        BatchReceiver::new(self.shared.subscribe())
+    }
+
+    /// Creates new receiver of batches, like [`CooldownSender::subscribe`], which holds at most
+    /// `capacity` undelivered batches. When the receiver lags behind, new batches are handled
+    /// according to `overflow`, so a slow consumer can't make the buffer grow without bounds.
+    /// Items of dropped batches are sent to the [dead letters](CooldownSender::dead_letters).
+    ///
+    /// # Example
+    /// ```
+    /// use std::num::NonZeroUsize;
+    /// use std::sync::mpsc::channel;
+    /// use std::time::Duration;
+    /// use cooldown_buffer::{cooldown_buffer, Overflow};
+    ///
+    /// let (tx, _rx) = cooldown_buffer(channel(), Duration::from_millis(10));
+    /// let capacity = NonZeroUsize::new(1).unwrap();
+    /// let rx = tx.subscribe_bounded(capacity, Overflow::Merge(|older, newer| older.extend(newer)));
+    /// tx.send(1).unwrap();
+    /// tx.flush().unwrap();
+    /// tx.send(2).unwrap();
+    /// std::thread::sleep(Duration::from_millis(50));
+    /// assert_eq!(rx.recv().unwrap(), vec![1, 2]);
+    /// ```
+    #[must_use]
+    pub fn subscribe_bounded(
+        &self,
+        capacity: NonZeroUsize,
+        overflow: Overflow<B>,
+    ) -> BatchReceiver<T, B>
+    where
+        T: 'static + Clone + Send,
+        B: 'static + AsRef<[T]>,
+    {
+        // Batches are dropped on the buffer thread, so they can't go through the item channel,
+        // it may be full.
+        let (dropped_tx, dropped_rx) = outbox::unbounded();
+        let dropped = move |batch: B| {
+            let _ = dropped_tx.send(batch.as_ref().to_vec());
+        };
+        let rx = self.shared.subscribe_bounded(capacity, overflow, dropped);
+        // When the buffer is not running, nothing is going to be dropped.
+        let _ = self.command(Command::WatchDropped(dropped_rx));
+        BatchReceiver::new(rx)
+    }
+
+    /// Creates a handle to the most recently emitted batch, see [`BatchWatch`]. Useful for
+    /// consumers which only care about the newest state, e.g. status displays.
+    #[must_use]
+    pub fn watch(&self) -> BatchWatch<T, B>
+    where
+        B: 'static + Send + Sync,
+    {
+        let watch = BatchWatch::new();
+        self.shared.watch(watch.updater());
+        watch
+    }
+
+    /// Creates a handle waiting for emitted batches without receiving them, see
+    /// [`FlushWaiter`].
+    #[must_use]
+    pub fn flush_waiter(&self) -> FlushWaiter {
+        FlushWaiter::new(self.shared.flushes().clone())
+    }
+
+    /// Attaches the buffer to the cancellation token. When the token is cancelled, the buffer
+    /// emits pending items and stops. All receivers get disconnected then.
+    ///
+    /// The token doesn't keep the buffer running, the buffer stops as usual when all the senders
+    /// are dropped.
+    pub fn cancel_on(&self, token: &CancellationToken)
+    where
+        T: 'static + Send,
+    {
+        let tx = Arc::downgrade(&self.tx);
+        token.on_cancel(move || {
+            if let Some(tx) = Weak::upgrade(&tx) {
+                let _ = tx.send(Message(Command::Shutdown));
+            }
+        });
+    }
+
+    /// Stops the buffer, emitting pending items unless the buffer thread doesn't get to them
+    /// within `timeout`, e.g. because it's still busy with items sent before. Then they are
+    /// dropped and sent to the [dead letters](CooldownSender::dead_letters). So are the items a
+    /// receiver with [`Overflow::Block`] didn't make room for within `timeout`. Returns the number
+    /// of dropped items. All receivers get disconnected. Timeout too big to be represented, like
+    /// `Duration::MAX`, waits for the buffer thread without a limit.
+    ///
+    /// Sending anything with any of the senders fails once the shutdown was requested.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] when the buffer thread didn't respond within `timeout`. It still
+    /// stops once it gets to it, dropping pending items. Returns [`Error::Disconnected`] when the
+    /// buffer thread is not running anymore.
+    pub fn shutdown_timeout(&self, timeout: Duration) -> Result<usize, Error> {
+        let (reply_tx, reply_rx) = channel();
+        let deadline = Instant::now().checked_add(timeout);
+        self.command(Command::ShutdownBy(deadline, reply_tx))?;
+        self.shared.shut_down();
+        let reply = match deadline {
+            Some(_) => reply_rx.recv_timeout(timeout),
+            None => reply_rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match reply {
+            Ok(dropped) => Ok(dropped),
+            Err(RecvTimeoutError::Timeout) => Err(Error::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(Error::Disconnected),
+        }
+    }
+
+    fn command(&self, command: Command<T>) -> Result<(), Error> {
+        if self.shared.is_shutting_down() {
+            return Err(Error::Disconnected);
+        }
+        self.tx
+            .send(Message(command))
+            .map_err(|_| Error::Disconnected)
+    }
+}
+
+impl<T, B> Clone for CooldownSender<T, B> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Msg;
+    use crate::outbox::Overflow;
+    use crate::window::until_next_boundary;
+    use crate::{cooldown_buffer, cooldown_buffer_fixed, Error};
+    use std::num::NonZeroUsize;
+    use std::sync::mpsc::{channel, sync_channel};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn flush_emits_without_waiting_for_cooldown() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+
+        // when
+        tx.send_many(vec![1, 2, 3]).unwrap();
+        tx.flush().unwrap();
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(buf, Ok(vec![1, 2, 3]));
+        assert_eq!(tx.pending_len(), 0);
+    }
+
+    #[test]
+    fn huge_cooldown_waits_for_flush() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::MAX);
+
+        // when
+        tx.send(1).unwrap();
+        let cooling = rx.recv_timeout(Duration::from_millis(50));
+        tx.flush().unwrap();
+        let flushed = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(cooling, Err(Error::Timeout));
+        assert_eq!(flushed, Ok(vec![1]));
+    }
+
+    #[test]
+    fn paused_buffer_holds_items_until_resumed() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+
+        // when
+        tx.pause().unwrap();
+        tx.send_many(vec![1, 2]).unwrap();
+        let held = rx.recv_timeout(Duration::from_millis(100));
+        tx.resume().unwrap();
+        let resumed = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(held, Err(Error::Timeout));
+        assert_eq!(resumed, Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn envelopes_control_buffer_through_item_channel() {
+        // given
+        let (item_tx, item_rx) = channel();
+        let (_tx, rx) = cooldown_buffer((item_tx.clone(), item_rx), Duration::from_millis(20));
+
+        // when
+        item_tx.send(Msg::Pause.into()).unwrap();
+        item_tx.send(Msg::Item(1).into()).unwrap();
+        let held = rx.recv_timeout(Duration::from_millis(100));
+        item_tx
+            .send(Msg::SetCooldown(Duration::from_secs(60)).into())
+            .unwrap();
+        item_tx.send(Msg::Resume.into()).unwrap();
+        item_tx.send(Msg::Item(2).into()).unwrap();
+        let cooling = rx.recv_timeout(Duration::from_millis(100));
+        item_tx.send(Msg::Flush.into()).unwrap();
+        let flushed = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(held, Err(Error::Timeout));
+        assert_eq!(cooling, Err(Error::Timeout));
+        assert_eq!(flushed, Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn bounded_channel_can_be_used() {
+        // given
+        let (tx, rx) = cooldown_buffer(sync_channel(1), Duration::from_millis(20));
+
+        // when
+        for n in 1..=3 {
+            tx.send(n).unwrap();
+        }
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(buf, Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn batches_dropped_by_lagging_receiver_become_dead_letters() {
+        // given
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        let dead_letters = tx.dead_letters();
+        let lagging = tx.subscribe_bounded(NonZeroUsize::MIN, Overflow::DropOldest);
+
+        // when
+        for n in 1..=3 {
+            tx.send(n).unwrap();
+            tx.flush().unwrap();
+        }
+        let dropped = [
+            dead_letters.recv_timeout(Duration::from_millis(500)),
+            dead_letters.recv_timeout(Duration::from_millis(500)),
+        ];
+
+        // then
+        assert_eq!(dropped, [Ok(vec![1]), Ok(vec![2])]);
+        assert_eq!(lagging.try_recv(), Ok(vec![3]));
+    }
+
+    #[test]
+    fn dropping_batches_does_not_block_on_full_item_channel() {
+        // given
+        let (tx, _rx) = cooldown_buffer_fixed::<u32, 1>(sync_channel(1), Duration::from_secs(10));
+        let _lagging = tx.subscribe_bounded(NonZeroUsize::MIN, Overflow::DropOldest);
+        let dead_letters = tx.dead_letters();
+
+        // when
+        let producers: Vec<_> = (0..4)
+            .map(|_| {
+                let tx = tx.clone();
+                thread::spawn(move || (0..50).for_each(|n| tx.send(n).unwrap()))
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(500));
+        let finished = producers.iter().all(thread::JoinHandle::is_finished);
+        tx.flush().unwrap();
+
+        // then
+        assert!(finished);
+        producers.into_iter().for_each(|p| p.join().unwrap());
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(
+            std::iter::from_fn(|| dead_letters.try_recv().ok()).count(),
+            199
+        );
+    }
+
+    #[test]
+    fn blocked_receiver_does_not_stop_subscribing() {
+        // given
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        let blocking = tx.subscribe_bounded(NonZeroUsize::MIN, Overflow::Block);
+        tx.send_many(vec![1, 2]).unwrap();
+        tx.flush().unwrap();
+        tx.send(3).unwrap();
+        tx.flush().unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        // when
+        let subscriber = {
+            let tx = tx.clone();
+            thread::spawn(move || tx.subscribe())
+        };
+        thread::sleep(Duration::from_millis(50));
+        let subscribed = subscriber.is_finished();
+
+        // then
+        assert!(subscribed);
+        assert_eq!(blocking.recv(), Ok(vec![1, 2]));
+        assert_eq!(blocking.recv(), Ok(vec![3]));
+    }
+
+    #[test]
+    fn flush_does_nothing_when_buffer_is_empty() {
+        // given
+        let (tx, rx) = cooldown_buffer::<u32>(channel(), Duration::from_secs(10));
+
+        // when
+        tx.flush().unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        // then
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn clear_drops_pending_items() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(50));
+
+        // when
+        tx.send_many(vec![1, 2]).unwrap();
+        tx.clear().unwrap();
+        tx.send(3).unwrap();
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(buf, Ok(vec![3]));
+    }
+
+    #[test]
+    fn clear_stops_the_cooldown() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+
+        // when
+        tx.send(1).unwrap();
+        tx.clear().unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        // then
+        assert!(rx.try_recv().is_err());
+        assert_eq!(tx.status().flush_in, None);
+    }
+
+    #[test]
+    fn cleared_items_are_sent_to_dead_letters() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+        let dead_letters = tx.dead_letters();
+
+        // when
+        tx.send_many(vec![1, 2]).unwrap();
+        tx.clear().unwrap();
+        tx.send(3).unwrap();
+        let discarded = dead_letters.recv_timeout(Duration::from_millis(500));
+        let emitted = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(discarded, Ok(vec![1, 2]));
+        assert_eq!(emitted, Ok(vec![3]));
+        assert!(dead_letters.try_recv().is_err());
+    }
+
+    #[test]
+    fn pending_len_counts_buffered_items() {
+        // given
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+
+        // when
+        tx.send_many(vec![1, 2]).unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        // then
+        assert_eq!(tx.pending_len(), 2);
+    }
+
+    #[test]
+    fn snapshot_leaves_buffer_untouched() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(100));
+
+        // when
+        tx.send_many(vec![1, 2]).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        let snapshot = tx.snapshot();
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(snapshot, vec![1, 2]);
+        assert_eq!(buf, Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn snapshot_includes_items_sent_before() {
+        // given
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+
+        // when
+        tx.send_many(vec![1, 2]).unwrap();
+        tx.send(3).unwrap();
+        let snapshot = tx.snapshot();
+
+        // then
+        assert_eq!(snapshot, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn batch_is_emitted_together_with_single_items() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(50));
+
+        // when
+        tx.send(1).unwrap();
+        tx.send_batch(vec![2, 3]).unwrap();
+        tx.send_batch(Vec::new()).unwrap();
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(buf, Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn every_subscriber_gets_each_batch() {
+        // given
+        let (tx, rx1) = cooldown_buffer(channel(), Duration::from_millis(20));
+        let rx2 = tx.subscribe();
+
+        // when
+        tx.send_many(vec![1, 2]).unwrap();
+        let buf1 = rx1.recv_timeout(Duration::from_millis(500));
+        let buf2 = rx2.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(buf1, Ok(vec![1, 2]));
+        assert_eq!(buf2, Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn dropped_subscriber_does_not_break_others() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+        drop(tx.subscribe());
+
+        // when
+        tx.send(1).unwrap();
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(buf, Ok(vec![1]));
+    }
+
+    #[test]
+    fn status_reports_pending_items_and_time_until_flush() {
+        // given
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        let idle = tx.status();
+
+        // when
+        tx.send_many(vec![1, 2]).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        let busy = tx.status();
+
+        // then
+        assert_eq!(idle.pending, 0);
+        assert_eq!(idle.flush_in, None);
+        assert_eq!(busy.pending, 2);
+        assert!(busy.flush_in.unwrap() > Duration::from_secs(9));
+    }
+
+    #[test]
+    fn status_is_idle_after_flush() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        tx.send(1).unwrap();
+
+        // when
+        tx.flush().unwrap();
+        rx.recv_timeout(Duration::from_millis(500)).unwrap();
+        let status = tx.status();
+
+        // then
+        assert_eq!(status.pending, 0);
+        assert_eq!(status.flush_in, None);
+    }
+
+    #[test]
+    fn time_until_flush_counts_down() {
+        // given
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        let idle = tx.time_until_flush();
+
+        // when
+        tx.send(1).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        let first = tx.time_until_flush().unwrap();
+        thread::sleep(Duration::from_millis(50));
+        let second = tx.time_until_flush().unwrap();
+
+        // then
+        assert_eq!(idle, None);
+        assert!(first <= Duration::from_secs(10));
+        assert!(second < first);
+    }
+
+    #[test]
+    fn stats_count_emitted_batches_and_items() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(50));
+
+        // when
+        tx.send_many(vec![1, 2, 3]).unwrap();
+        rx.recv_timeout(Duration::from_millis(500)).unwrap();
+        tx.send(4).unwrap();
+        tx.flush().unwrap();
+        rx.recv_timeout(Duration::from_millis(500)).unwrap();
+        let stats = tx.stats();
+
+        // then
+        assert_eq!(stats.batch_size.count(), 2);
+        assert_eq!(stats.batch_size.max(), 3);
+        assert_eq!(stats.latency_us.count(), 4);
+        assert!(stats.latency_us.max() >= 50_000);
+        assert!(stats.latency_us.percentile(0.25) < 50_000);
+    }
+
+    #[test]
+    fn aligned_flush_does_not_wait_for_cooldown() {
+        // given
+        let period = Duration::from_millis(100);
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        tx.align_flushes(Some(period)).unwrap();
+        thread::sleep(until_next_boundary(period) + Duration::from_millis(10));
+
+        // when
+        tx.send(1).unwrap();
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+        let emitted_at = until_next_boundary(period);
+
+        // then
+        assert_eq!(buf, Ok(vec![1]));
+        assert!(emitted_at > Duration::from_millis(70));
+    }
+
+    #[test]
+    fn oversized_batch_is_emitted_in_chunks() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+
+        // when
+        tx.set_chunk_size(NonZeroUsize::new(2)).unwrap();
+        tx.send_many(1..=5).unwrap();
+        let batches: Vec<_> = (0..3).map(|_| rx.recv().unwrap()).collect();
+
+        // then
+        assert_eq!(batches, vec![vec![1, 2], vec![3, 4], vec![5]]);
+        assert_eq!(tx.stats().batch_size.count(), 3);
+    }
+
+    #[test]
+    fn burst_cooldown_is_used_above_threshold() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(20));
+        tx.set_burst_cooldown(2, Duration::from_secs(10)).unwrap();
+
+        // when
+        tx.send(1).unwrap();
+        let isolated = rx.recv_timeout(Duration::from_millis(500));
+        tx.send_many(vec![2, 3, 4]).unwrap();
+        let burst = rx.recv_timeout(Duration::from_millis(200));
+
+        // then
+        assert_eq!(isolated, Ok(vec![1]));
+        assert!(burst.is_err());
+        assert_eq!(tx.pending_len(), 3);
+    }
+
+    #[test]
+    fn requeued_items_ride_along_with_the_next_batch() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(50));
+        tx.send_many(vec![1, 2]).unwrap();
+        let failed = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+
+        // when
+        tx.send(3).unwrap();
+        tx.requeue(failed).unwrap();
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(buf, Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn shutdown_timeout_emits_pending_items() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        tx.send_many(vec![1, 2]).unwrap();
+
+        // when
+        let dropped = tx.shutdown_timeout(Duration::from_secs(1));
+
+        // then
+        assert_eq!(dropped, Ok(0));
+        assert_eq!(rx.recv_timeout(Duration::from_millis(500)), Ok(vec![1, 2]));
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(500)),
+            Err(Error::Disconnected)
+        );
+        assert!(tx.send(3).is_err());
+    }
+
+    #[test]
+    fn huge_shutdown_timeout_waits_without_limit() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        tx.send_many(vec![1, 2]).unwrap();
+
+        // when
+        let dropped = tx.shutdown_timeout(Duration::MAX);
+
+        // then
+        assert_eq!(dropped, Ok(0));
+        assert_eq!(rx.recv_timeout(Duration::from_millis(500)), Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn shutdown_timeout_drops_items_when_deadline_passed() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        let dead_letters = tx.dead_letters();
+        tx.send_many(vec![1, 2]).unwrap();
+
+        // when
+        let dropped = tx.shutdown_timeout(Duration::ZERO);
+
+        // then
+        assert!(matches!(dropped, Ok(2) | Err(Error::Timeout)));
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(500)),
+            Err(Error::Disconnected)
+        );
+        assert_eq!(
+            dead_letters.recv_timeout(Duration::from_millis(500)),
+            Ok(vec![1, 2])
+        );
+    }
+
+    #[test]
+    fn shutdown_timeout_gives_up_on_blocked_receiver() {
+        // given
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        let dead_letters = tx.dead_letters();
+        let _blocked = tx.subscribe_bounded(NonZeroUsize::MIN, Overflow::Block);
+        let mut flushes = tx.flush_waiter();
+        tx.send(1).unwrap();
+        tx.flush().unwrap();
+        flushes.wait_timeout(Duration::from_millis(500)).unwrap();
+        tx.send_many(vec![2, 3]).unwrap();
+
+        // when
+        let dropped = tx.shutdown_timeout(Duration::from_millis(100));
+
+        // then
+        assert!(matches!(dropped, Ok(2) | Err(Error::Timeout)));
+        assert_eq!(
+            dead_letters.recv_timeout(Duration::from_millis(500)),
+            Ok(vec![2, 3])
+        );
+        assert_eq!(tx.send(4), Err(Error::Disconnected));
+    }
+
+    #[test]
+    fn drain_hands_pending_items_to_the_caller() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(50));
+        tx.send_many(vec![1, 2]).unwrap();
+
+        // when
+        let drained = tx.drain();
+
+        // then
+        assert_eq!(drained, vec![1, 2]);
+        assert_eq!(tx.status().flush_in, None);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(200)),
+            Err(Error::Timeout)
+        );
+    }
+
+    #[test]
+    fn set_cooldown_applies_to_next_items() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+
+        // when
+        tx.set_cooldown(Duration::from_millis(50)).unwrap();
+        tx.send(1).unwrap();
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(buf, Ok(vec![1]));
+    }
+}
@@ -0,0 +1,258 @@
+use crate::notify::FlushSignal;
+use crate::outbox::{self, outbox, OutboxReceiver, OutboxSender, Overflow, Unsent};
+use crate::stats::{Recorder, Stats};
+use crate::sync::lock;
+use std::fmt::{self, Debug};
+use std::io;
+use std::mem;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const NOT_SCHEDULED: u64 = u64::MAX;
+
+/// State published by the buffer thread for the handles. Buffered items are not part of it, they
+/// are owned by the buffer thread alone (see [`Buffer`](crate::buffer::Buffer)), so sending
+/// items never waits for a lock.
+#[derive(Debug)]
+pub(crate) struct Shared<B> {
+    pending: AtomicUsize,
+    /// Nanoseconds since `started` when the pending items are going to be emitted.
+    flush_at: AtomicU64,
+    /// Nanoseconds since `started` when the buffer stopped being empty.
+    pending_since: AtomicU64,
+    started: Instant,
+    latency_us: Recorder,
+    batch_size: Recorder,
+    /// Locked only when subscribing and once per emitted batch.
+    subscribers: Mutex<Vec<Subscriber<B>>>,
+    flushes: Arc<FlushSignal>,
+    /// Whether the emitted batch was acknowledged, see
+    /// [`cooldown_buffer_acked`](crate::cooldown_buffer_acked).
+    acked: Arc<AtomicBool>,
+    /// The last failure of the write-ahead log, see [`Wal`](crate::Wal).
+    wal_error: Mutex<Option<io::Error>>,
+    /// Whether the shutdown was requested, see
+    /// [`CooldownSender::shutdown_timeout`](crate::CooldownSender::shutdown_timeout).
+    shutting_down: AtomicBool,
+}
+
+#[derive(Debug)]
+enum Subscriber<B> {
+    Queue(OutboxSender<B>),
+    Watch(Updater<B>),
+}
+
+/// Replaces the latest batch of [`BatchWatch`](crate::BatchWatch). Returns whether the watch is
+/// still there.
+struct Updater<B>(Box<dyn Fn(B) -> bool + Send>);
+
+impl<B> Debug for Updater<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Updater")
+    }
+}
+
+enum Delivery {
+    Taken,
+    Gone,
+    TimedOut,
+}
+
+impl<B> Subscriber<B> {
+    /// Waits for room in the queue only until the `deadline`.
+    fn send(&self, batch: B, deadline: Option<Instant>) -> Delivery {
+        let taken = match self {
+            Self::Queue(tx) => match tx.send_until(batch, deadline) {
+                Ok(()) => true,
+                Err(Unsent::Disconnected(_)) => false,
+                Err(Unsent::TimedOut(_)) => return Delivery::TimedOut,
+            },
+            Self::Watch(Updater(update)) => update(batch),
+        };
+        if taken {
+            Delivery::Taken
+        } else {
+            Delivery::Gone
+        }
+    }
+}
+
+impl<B> Shared<B> {
+    pub(crate) fn new() -> Self {
+        Self {
+            pending: AtomicUsize::new(0),
+            flush_at: AtomicU64::new(NOT_SCHEDULED),
+            pending_since: AtomicU64::new(NOT_SCHEDULED),
+            started: Instant::now(),
+            latency_us: Recorder::new(),
+            batch_size: Recorder::new(),
+            subscribers: Mutex::new(Vec::new()),
+            flushes: Arc::default(),
+            acked: Arc::default(),
+            wal_error: Mutex::new(None),
+            shutting_down: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn pending_len(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_pending_len(&self, len: usize) {
+        let before = self.pending.swap(len, Ordering::Relaxed);
+        if len == 0 {
+            self.pending_since.store(NOT_SCHEDULED, Ordering::Relaxed);
+        } else if before == 0 {
+            let now = self.nanos(Some(Instant::now()));
+            self.pending_since.store(now, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn flush_in(&self) -> Option<Duration> {
+        let flush_at = self.instant(self.flush_at.load(Ordering::Relaxed))?;
+        Some(flush_at.saturating_duration_since(Instant::now()))
+    }
+
+    /// Returns when the buffer stopped being empty, `None` when it's empty.
+    pub(crate) fn pending_since(&self) -> Option<Instant> {
+        self.instant(self.pending_since.load(Ordering::Relaxed))
+    }
+
+    /// Records when the pending items are going to be emitted. It's only used for reporting.
+    pub(crate) fn schedule(&self, flush_at: Option<Instant>) {
+        self.flush_at.store(self.nanos(flush_at), Ordering::Relaxed);
+    }
+
+    fn nanos(&self, instant: Option<Instant>) -> u64 {
+        instant.map_or(NOT_SCHEDULED, |instant| {
+            let since_start = instant.saturating_duration_since(self.started);
+            u64::try_from(since_start.as_nanos()).unwrap_or(NOT_SCHEDULED - 1)
+        })
+    }
+
+    fn instant(&self, nanos: u64) -> Option<Instant> {
+        match nanos {
+            NOT_SCHEDULED => None,
+            nanos => Some(self.started + Duration::from_nanos(nanos)),
+        }
+    }
+
+    /// Records the emission of the batch with items received at `arrivals`.
+    pub(crate) fn record_emission(&self, arrivals: &[Instant]) {
+        let now = Instant::now();
+        for arrival in arrivals {
+            let latency = now.saturating_duration_since(*arrival).as_micros();
+            self.latency_us
+                .record(u64::try_from(latency).unwrap_or(u64::MAX));
+        }
+        self.batch_size
+            .record(u64::try_from(arrivals.len()).unwrap_or(u64::MAX));
+    }
+
+    pub(crate) fn stats(&self) -> Stats {
+        Stats {
+            latency_us: self.latency_us.histogram(),
+            batch_size: self.batch_size.histogram(),
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> OutboxReceiver<B> {
+        let (tx, rx) = outbox::unbounded();
+        lock(&self.subscribers).push(Subscriber::Queue(tx));
+        rx
+    }
+
+    /// Subscribes with at most `capacity` undelivered batches, see [`Overflow`]. Batches dropped
+    /// to make room for the new ones are passed to `dropped`.
+    pub(crate) fn subscribe_bounded(
+        &self,
+        capacity: NonZeroUsize,
+        overflow: Overflow<B>,
+        dropped: impl Fn(B) + Send + 'static,
+    ) -> OutboxReceiver<B> {
+        let (tx, rx) = outbox(capacity, overflow);
+        let tx = tx.on_dropped(dropped);
+        lock(&self.subscribers).push(Subscriber::Queue(tx));
+        rx
+    }
+
+    pub(crate) fn watch(&self, update: Box<dyn Fn(B) -> bool + Send>) {
+        lock(&self.subscribers).push(Subscriber::Watch(Updater(update)));
+    }
+
+    /// Returns the flag set when the emitted batch is acknowledged.
+    pub(crate) fn acked(&self) -> &Arc<AtomicBool> {
+        &self.acked
+    }
+
+    /// Tells whether the emitted batch was acknowledged since the last call.
+    pub(crate) fn take_ack(&self) -> bool {
+        self.acked.swap(false, Ordering::Relaxed)
+    }
+
+    pub(crate) fn report_wal_error(&self, e: io::Error) {
+        *lock(&self.wal_error) = Some(e);
+    }
+
+    pub(crate) fn take_wal_error(&self) -> Option<io::Error> {
+        lock(&self.wal_error).take()
+    }
+
+    pub(crate) fn shut_down(&self) {
+        self.shutting_down.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn flushes(&self) -> &Arc<FlushSignal> {
+        &self.flushes
+    }
+
+    /// Disconnects all the subscribers.
+    pub(crate) fn close(&self) {
+        lock(&self.subscribers).clear();
+        self.flushes.close();
+    }
+
+    /// Sends the batch to every subscriber. Subscribers which are gone are forgotten. Returns
+    /// whether any subscriber got the batch.
+    pub(crate) fn publish(&self, batch: B) -> bool
+    where
+        B: Clone,
+    {
+        self.publish_until(batch, None).unwrap_or(false)
+    }
+
+    /// Publishes like [`Shared::publish`], but waits for blocked subscribers only until the
+    /// `deadline`. Returns `None` when any of them didn't take the batch in time, such
+    /// subscribers are forgotten as well.
+    pub(crate) fn publish_until(&self, batch: B, deadline: Option<Instant>) -> Option<bool>
+    where
+        B: Clone,
+    {
+        // Sent without the lock, so a blocked subscriber doesn't stop others from subscribing.
+        let mut subscribers = mem::take(&mut *lock(&self.subscribers));
+        let mut timed_out = false;
+        subscribers.retain(
+            |subscriber| match subscriber.send(batch.clone(), deadline) {
+                Delivery::Taken => true,
+                Delivery::Gone => false,
+                Delivery::TimedOut => {
+                    timed_out = true;
+                    false
+                }
+            },
+        );
+        let delivered = !subscribers.is_empty();
+        let mut current = lock(&self.subscribers);
+        let subscribed = mem::replace(&mut *current, subscribers);
+        current.extend(subscribed);
+        drop(current);
+        self.flushes.notify();
+        (!timed_out).then_some(delivered)
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const BUCKETS: usize = 65;
+
+/// Distributions of values observed by the buffer, returned by [`CooldownSender::stats`].
+///
+/// [`CooldownSender::stats`]: crate::CooldownSender::stats
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Stats {
+    /// Time in microseconds from receiving the item until the batch with it was emitted. When
+    /// the item replaced an older one (see
+    /// [`cooldown_buffer_latest`](crate::cooldown_buffer_latest)), the time is counted since
+    /// receiving the older one.
+    pub latency_us: Histogram,
+
+    /// Number of items in emitted batches.
+    pub batch_size: Histogram,
+}
+
+/// Distribution of observed values. Values are counted in buckets growing in powers of two, so
+/// percentiles are precise up to a factor of two.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: [u64; BUCKETS],
+    max: u64,
+}
+
+impl Histogram {
+    /// Returns the number of observed values.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Returns the greatest observed value, zero when nothing was observed.
+    #[must_use]
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// Returns the value which `quantile` (between `0.0` and `1.0`) of observed values don't
+    /// exceed, e.g. `0.99` for the 99th percentile. Returns zero when nothing was observed.
+    #[must_use]
+    pub fn percentile(&self, quantile: f64) -> u64 {
+        let count = self.count();
+        if count == 0 {
+            return 0;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, bucket_count) in self.buckets.iter().enumerate() {
+            seen += bucket_count;
+            if seen >= rank {
+                return upper_bound(bucket).min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+/// Lock-free collector of the [`Histogram`].
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    buckets: [AtomicU64; BUCKETS],
+    max: AtomicU64,
+}
+
+impl Recorder {
+    pub(crate) fn new() -> Self {
+        Self {
+            buckets: [0; BUCKETS].map(AtomicU64::new),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record(&self, value: u64) {
+        self.buckets[bucket_of(value)].fetch_add(1, Ordering::Relaxed);
+        self.max.fetch_max(value, Ordering::Relaxed);
+    }
+
+    pub(crate) fn histogram(&self) -> Histogram {
+        Histogram {
+            buckets: self
+                .buckets
+                .each_ref()
+                .map(|bucket| bucket.load(Ordering::Relaxed)),
+            max: self.max.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Bucket `0` holds zeros, bucket `n` holds values from `2^(n-1)` to `2^n - 1`.
+fn bucket_of(value: u64) -> usize {
+    (u64::BITS - value.leading_zeros()) as usize
+}
+
+fn upper_bound(bucket: usize) -> u64 {
+    match bucket {
+        0 => 0,
+        64.. => u64::MAX,
+        _ => (1 << bucket) - 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn percentiles_are_within_factor_of_two() {
+        // given
+        let recorder = Recorder::new();
+
+        // when
+        (1..=100).for_each(|value| recorder.record(value));
+        let histogram = recorder.histogram();
+
+        // then
+        assert_eq!(histogram.count(), 100);
+        assert_eq!(histogram.max(), 100);
+        assert!((50..100).contains(&histogram.percentile(0.5)));
+        assert_eq!(histogram.percentile(1.0), 100);
+    }
+
+    #[test]
+    fn empty_histogram_reports_zeros() {
+        // given
+        let recorder = Recorder::new();
+
+        // when
+        let histogram = recorder.histogram();
+
+        // then
+        assert_eq!(histogram.count(), 0);
+        assert_eq!(histogram.percentile(0.99), 0);
+    }
+
+    #[test]
+    fn extreme_values_are_counted() {
+        // given
+        let recorder = Recorder::new();
+
+        // when
+        recorder.record(0);
+        recorder.record(u64::MAX);
+        let histogram = recorder.histogram();
+
+        // then
+        assert_eq!(histogram.percentile(0.5), 0);
+        assert_eq!(histogram.percentile(1.0), u64::MAX);
+    }
+}
@@ -0,0 +1,15 @@
+use std::time::Duration;
+
+/// State of the buffer at the moment of the call, returned by [`CooldownSender::status`].
+///
+/// [`CooldownSender::status`]: crate::CooldownSender::status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Status {
+    /// Number of items buffered and not yet emitted.
+    pub pending: usize,
+
+    /// Time left until the pending items are emitted, `None` when nothing is scheduled. It's
+    /// only an estimate - new items may postpone the emission.
+    pub flush_in: Option<Duration>,
+}
@@ -0,0 +1,31 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Locks the mutex, also when it's poisoned. Nothing guarded by the crate's mutexes is left
+/// half-changed when the thread holding the lock panics, e.g. while cloning the emitted batch, so
+/// it's safe to use after the panic.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::panic;
+
+    #[test]
+    fn poisoned_mutex_can_be_locked() {
+        // given
+        let mutex = Mutex::new(1);
+        let _ = panic::catch_unwind(|| {
+            let _guard = mutex.lock().unwrap();
+            panic!("poisoning the mutex");
+        });
+
+        // when
+        let value = *lock(&mutex);
+
+        // then
+        assert!(mutex.is_poisoned());
+        assert_eq!(value, 1);
+    }
+}
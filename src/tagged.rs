@@ -0,0 +1,171 @@
+use crate::cooldown_buffer;
+use crate::error::Error;
+use crate::receiver::BatchReceiver;
+use crate::sender::{CooldownSender, ItemSender, Message};
+use std::fmt::Debug;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Item tagged with the producer which sent it, emitted by
+/// [`cooldown_buffer_tagged`](crate::cooldown_buffer_tagged).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tagged<T> {
+    /// Id of the [`TaggedSender`] which sent the item. Every clone of the sender has its own id.
+    pub producer: u64,
+
+    /// Position of the item among all the items sent by the producer, starting from zero.
+    pub seq: u64,
+
+    /// The item itself.
+    pub item: T,
+}
+
+/// Sending half of the buffer created with [`cooldown_buffer_tagged`]. It tags every item with
+/// the producer id and the next sequence number. Each clone is a separate producer, so it gets
+/// new id and counts its items from zero. Use one clone per thread - when the same producer is
+/// shared between threads, its items may arrive out of the sequence order.
+///
+/// It dereferences to [`CooldownSender`], so the buffer can be controlled as usual.
+#[derive(Debug)]
+pub struct TaggedSender<T> {
+    sender: CooldownSender<Tagged<T>>,
+    producers: Arc<AtomicU64>,
+    producer: u64,
+    next_seq: AtomicU64,
+}
+
+impl<T> TaggedSender<T> {
+    fn new(sender: CooldownSender<Tagged<T>>, producers: Arc<AtomicU64>) -> Self {
+        let producer = producers.fetch_add(1, Ordering::Relaxed);
+        Self {
+            sender,
+            producers,
+            producer,
+            next_seq: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the id of this producer.
+    #[must_use]
+    pub fn producer(&self) -> u64 {
+        self.producer
+    }
+
+    /// Tags the item and sends it to the buffer, see [`CooldownSender::send`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    pub fn send(&self, item: T) -> Result<(), Error> {
+        self.sender.send(self.tag(item))
+    }
+
+    /// Tags all the items and sends them to the buffer at once, see
+    /// [`CooldownSender::send_many`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Disconnected`] when the buffer thread is not running anymore.
+    pub fn send_many<I>(&self, items: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.sender
+            .send_many(items.into_iter().map(|item| self.tag(item)))
+    }
+
+    fn tag(&self, item: T) -> Tagged<T> {
+        Tagged {
+            producer: self.producer,
+            seq: self.next_seq.fetch_add(1, Ordering::Relaxed),
+            item,
+        }
+    }
+}
+
+impl<T> Clone for TaggedSender<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.sender.clone(), self.producers.clone())
+    }
+}
+
+impl<T> Deref for TaggedSender<T> {
+    type Target = CooldownSender<Tagged<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.sender
+    }
+}
+
+/// Works like [`cooldown_buffer`], but items are tagged with the producer which sent them and
+/// its sequence number, see [`Tagged`]. It allows verifying the order of items sent by each
+/// producer when many clones of the sender are used from different threads.
+#[must_use]
+pub fn cooldown_buffer_tagged<T>(
+    channel: (
+        impl Into<ItemSender<Tagged<T>>>,
+        Receiver<Message<Tagged<T>>>,
+    ),
+    cooldown_time: Duration,
+) -> (TaggedSender<T>, BatchReceiver<Tagged<T>>)
+where
+    T: 'static + Clone + Debug + Send,
+{
+    let (sender, receiver) = cooldown_buffer(channel, cooldown_time);
+    let sender = TaggedSender::new(sender, Arc::new(AtomicU64::new(0)));
+    (sender, receiver)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    #[test]
+    fn items_are_tagged_with_producer_and_sequence() {
+        // given
+        let (tx1, rx) = cooldown_buffer_tagged(channel(), Duration::from_millis(50));
+        let tx2 = tx1.clone();
+
+        // when
+        tx1.send("a").unwrap();
+        tx2.send("b").unwrap();
+        tx1.send("c").unwrap();
+        let buf = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+
+        // then
+        let tags: Vec<_> = buf.iter().map(|t| (t.producer, t.seq, t.item)).collect();
+        assert_eq!(tags, vec![(0, 0, "a"), (1, 0, "b"), (0, 1, "c")]);
+    }
+
+    #[test]
+    fn order_of_each_producer_is_kept_across_threads() {
+        // given
+        let (tx, rx) = cooldown_buffer_tagged(channel(), Duration::from_millis(50));
+
+        // when
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let tx = tx.clone();
+                thread::spawn(move || (0..100).for_each(|n| tx.send(n).unwrap()))
+            })
+            .collect();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+        let buf = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+
+        // then
+        assert_eq!(buf.len(), 400);
+        for producer in 1..=4 {
+            let seqs: Vec<_> = buf
+                .iter()
+                .filter(|t| t.producer == producer)
+                .map(|t| t.seq)
+                .collect();
+            assert_eq!(seqs, (0..100).collect::<Vec<_>>());
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use std::fmt::{self, Debug};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::path::Path;
+
+type Encode<T> = Box<dyn Fn(&T) -> Vec<u8> + Send>;
+
+/// Write-ahead log used by [`cooldown_buffer_durable`](crate::cooldown_buffer_durable) to keep
+/// buffered items on disk until they are emitted.
+///
+/// Each received item is appended to the log file and the log is truncated when the batch is
+/// handed to at least one receiver. Items left in the file (e.g. after a crash) are recovered
+/// when the log is opened again and re-emitted by the buffer right after the start.
+///
+/// Records are written straight to the file without `fsync`, so they survive the process crash,
+/// but not necessarily the OS crash. When writing to the log fails, the item is still buffered
+/// and emitted, but it's not durable. The error is reported by
+/// [`CooldownSender::take_wal_error`](crate::CooldownSender::take_wal_error).
+pub struct Wal<T> {
+    file: File,
+    encode: Encode<T>,
+    recovered: Vec<T>,
+    /// Length of the complete records in the file.
+    len: u64,
+}
+
+impl<T> Wal<T> {
+    /// Opens (or creates) the log file at `path` and recovers items left there.
+    ///
+    /// Items are stored as bytes returned from `encode` and read back with `decode`. Incomplete
+    /// record at the end of the file, left by interrupted write, is ignored and cut off the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns error when the file can't be opened or read, or when `decode` returns `None` for
+    /// any of the records.
+    pub fn open<P, E, D>(path: P, encode: E, decode: D) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        E: 'static + Fn(&T) -> Vec<u8> + Send,
+        D: Fn(&[u8]) -> Option<T>,
+    {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        let recovered = records(&content)
+            .map(|record| decode(record).ok_or_else(|| invalid_data("undecodable WAL record")))
+            .collect::<io::Result<_>>()?;
+        let len = records(&content)
+            .map(|record| record.len() as u64 + 4)
+            .sum();
+        if len < content.len() as u64 {
+            file.set_len(len)?;
+        }
+        Ok(Self {
+            file,
+            encode: Box::new(encode),
+            recovered,
+            len,
+        })
+    }
+
+    pub(crate) fn take_recovered(&mut self) -> Vec<T> {
+        mem::take(&mut self.recovered)
+    }
+
+    /// Appends the item to the log. When it fails, partly written record is cut off, so the
+    /// following records can still be recovered.
+    pub(crate) fn append(&mut self, item: &T) -> io::Result<()> {
+        let frame =
+            frame(&(self.encode)(item)).ok_or_else(|| invalid_data("WAL record too big"))?;
+        if let Err(e) = self.file.write_all(&frame) {
+            // Best effort, the original error is the one worth reporting.
+            let _ = self.file.set_len(self.len);
+            return Err(e);
+        }
+        self.len += frame.len() as u64;
+        Ok(())
+    }
+
+    pub(crate) fn truncate(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+impl<T> Debug for Wal<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Wal")
+            .field("file", &self.file)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Prefixes the record with its length, as little-endian `u32`. Returns `None` when the record is
+/// too big for that.
+pub(crate) fn frame(record: &[u8]) -> Option<Vec<u8>> {
+    let len = u32::try_from(record.len()).ok()?;
+    let mut frame = Vec::with_capacity(record.len() + 4);
+    frame.extend_from_slice(&len.to_le_bytes());
+    frame.extend_from_slice(record);
+    Some(frame)
+}
+
+fn records(mut content: &[u8]) -> impl Iterator<Item = &[u8]> {
+    std::iter::from_fn(move || {
+        let (len, rest) = content.split_first_chunk::<4>()?;
+        let len = u32::from_le_bytes(*len) as usize;
+        if rest.len() < len {
+            return None;
+        }
+        let (record, rest) = rest.split_at(len);
+        content = rest;
+        Some(record)
+    })
+}
+
+pub(crate) fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cooldown_buffer_durable;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::Duration;
+
+    fn wal_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("cooldown-buffer-{}-{name}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn open(path: &Path) -> Wal<u32> {
+        Wal::open(
+            path,
+            |n: &u32| n.to_le_bytes().to_vec(),
+            |bytes| Some(u32::from_le_bytes(bytes.try_into().ok()?)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn unflushed_items_are_recovered() {
+        // given
+        let path = wal_path("recovered");
+        let (tx, _rx) = cooldown_buffer_durable(channel(), Duration::from_secs(10), open(&path));
+        tx.send_many(vec![1, 2]).unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        // when
+        let (_tx, rx) = cooldown_buffer_durable(channel(), Duration::from_secs(10), open(&path));
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(buf, Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn log_is_truncated_after_emitting() {
+        // given
+        let path = wal_path("truncated");
+        let (tx, rx) = cooldown_buffer_durable(channel(), Duration::from_millis(20), open(&path));
+
+        // when
+        tx.send_many(vec![1, 2]).unwrap();
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(buf, Ok(vec![1, 2]));
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn items_sent_after_incomplete_record_are_recovered() {
+        // given
+        let path = wal_path("torn");
+        fs::write(&path, [4, 0, 0]).unwrap();
+        let (tx, _rx) = cooldown_buffer_durable(channel(), Duration::from_secs(10), open(&path));
+        tx.send_many(vec![7, 8]).unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        // when
+        let (_tx, rx) = cooldown_buffer_durable(channel(), Duration::from_secs(10), open(&path));
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(buf, Ok(vec![7, 8]));
+    }
+
+    #[test]
+    fn failed_append_is_reported_and_item_still_emitted() {
+        // given
+        let path = wal_path("read-only");
+        let mut wal = open(&path);
+        wal.file = File::open(&path).unwrap();
+        let (tx, rx) = cooldown_buffer_durable(channel(), Duration::from_millis(20), wal);
+
+        // when
+        tx.send(1).unwrap();
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(buf, Ok(vec![1]));
+        assert!(tx.take_wal_error().is_some());
+        assert!(tx.take_wal_error().is_none());
+    }
+
+    #[test]
+    fn incomplete_record_is_ignored() {
+        // given
+        let path = wal_path("incomplete");
+        fs::write(&path, [4, 0, 0, 0, 7, 0, 0, 0, 4, 0, 0]).unwrap();
+
+        // when
+        let mut wal = open(&path);
+
+        // then
+        assert_eq!(wal.take_recovered(), vec![7]);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 8);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::sync::lock;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, Weak};
+
+/// Handle to the most recently emitted batch, created with
+/// [`CooldownSender::watch`](crate::CooldownSender::watch). Unlike [`BatchReceiver`], batches are
+/// not queued - each new batch replaces the previous one. It can be cloned, so any number of
+/// readers can look at the same batch.
+///
+/// [`BatchReceiver`]: crate::BatchReceiver
+#[derive(Debug)]
+pub struct BatchWatch<T, B = Vec<T>> {
+    latest: Arc<Mutex<Option<Arc<B>>>>,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T, B> BatchWatch<T, B> {
+    pub(crate) fn new() -> Self {
+        Self {
+            latest: Arc::new(Mutex::new(None)),
+            _item: PhantomData,
+        }
+    }
+
+    /// Returns the most recently emitted batch, `None` when nothing was emitted since the watch
+    /// was created. The last batch stays available after the buffer stopped.
+    #[must_use]
+    pub fn latest(&self) -> Option<Arc<B>> {
+        lock(&self.latest).clone()
+    }
+
+    /// Returns the function replacing the latest batch, which tells whether any watch is still
+    /// there. It doesn't keep the watch alive.
+    pub(crate) fn updater(&self) -> Box<dyn Fn(B) -> bool + Send>
+    where
+        B: 'static + Send + Sync,
+    {
+        let latest = Arc::downgrade(&self.latest);
+        Box::new(move |batch| match Weak::upgrade(&latest) {
+            Some(latest) => {
+                *lock(&latest) = Some(Arc::new(batch));
+                true
+            }
+            None => false,
+        })
+    }
+}
+
+impl<T, B> Clone for BatchWatch<T, B> {
+    fn clone(&self) -> Self {
+        Self {
+            latest: self.latest.clone(),
+            _item: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cooldown_buffer;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn watch_holds_only_the_latest_batch() {
+        // given
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        let watch = tx.watch();
+        let nothing = watch.latest();
+
+        // when
+        tx.send(1).unwrap();
+        tx.flush().unwrap();
+        tx.send(2).unwrap();
+        tx.flush().unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        // then
+        assert_eq!(nothing, None);
+        assert_eq!(watch.latest().as_deref(), Some(&vec![2]));
+        assert_eq!(watch.clone().latest().as_deref(), Some(&vec![2]));
+    }
+
+    #[test]
+    fn latest_batch_stays_after_buffer_stopped() {
+        // given
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        let watch = tx.watch();
+
+        // when
+        tx.send(1).unwrap();
+        drop(tx);
+        thread::sleep(Duration::from_millis(50));
+
+        // then
+        assert_eq!(watch.latest().as_deref(), Some(&vec![1]));
+    }
+}
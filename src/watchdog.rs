@@ -0,0 +1,78 @@
+use crate::shared::Shared;
+use std::sync::Weak;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Checks the buffer a few times per `threshold`, so the alarm is at most a quarter late.
+const CHECKS_PER_THRESHOLD: u32 = 4;
+
+/// Spawns the thread calling `on_stuck` when the buffer is not empty for longer than
+/// `threshold`. It stops once the buffer is gone.
+pub(crate) fn spawn<B, F>(shared: Weak<Shared<B>>, threshold: Duration, mut on_stuck: F)
+where
+    B: 'static + Send,
+    F: 'static + FnMut(Duration) + Send,
+{
+    let interval = threshold / CHECKS_PER_THRESHOLD;
+    thread::spawn(move || {
+        let mut alarmed: Option<Instant> = None;
+        loop {
+            thread::sleep(interval);
+            let Some(shared) = shared.upgrade() else {
+                return;
+            };
+            let Some(since) = shared.pending_since() else {
+                continue;
+            };
+            let pending_for = since.elapsed();
+            if pending_for >= threshold && alarmed != Some(since) {
+                alarmed = Some(since);
+                on_stuck(pending_for);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cooldown_buffer;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    #[test]
+    fn watchdog_fires_once_when_items_are_pending_too_long() {
+        // given
+        let (tx, _rx) = cooldown_buffer(channel(), Duration::from_secs(10));
+        let (alarm_tx, alarm_rx) = channel();
+        tx.watchdog(Duration::from_millis(50), move |pending_for| {
+            let _ = alarm_tx.send(pending_for);
+        });
+
+        // when
+        tx.send(1).unwrap();
+        let alarm = alarm_rx.recv_timeout(Duration::from_millis(500));
+        let repeated = alarm_rx.recv_timeout(Duration::from_millis(200));
+
+        // then
+        assert!(alarm.unwrap() >= Duration::from_millis(50));
+        assert!(repeated.is_err());
+    }
+
+    #[test]
+    fn watchdog_is_quiet_when_items_are_emitted() {
+        // given
+        let (tx, rx) = cooldown_buffer(channel(), Duration::from_millis(10));
+        let (alarm_tx, alarm_rx) = channel();
+        tx.watchdog(Duration::from_millis(100), move |pending_for| {
+            let _ = alarm_tx.send(pending_for);
+        });
+
+        // when
+        tx.send(1).unwrap();
+        rx.recv_timeout(Duration::from_millis(500)).unwrap();
+        let alarm = alarm_rx.recv_timeout(Duration::from_millis(300));
+
+        // then
+        assert!(alarm.is_err());
+    }
+}
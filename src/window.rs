@@ -0,0 +1,340 @@
+use crate::buffer::Buffer;
+use crate::receiver::BatchReceiver;
+use crate::sender::{Command, CooldownSender, ItemSender, Message};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::iter;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Alternative to [`cooldown_buffer`](crate::cooldown_buffer) batching items by time instead of
+/// activity. Every `slide`, all the items received within the last `window` are emitted, so the
+/// same item is usually part of many consecutive batches. Nothing is emitted when no item was
+/// received within the `window`.
+///
+/// [`CooldownSender::flush`] emits the current window right away and
+/// [`CooldownSender::set_cooldown`] has no effect. When all the senders are dropped, the current
+/// window is emitted one last time. Slide too long to be represented, like `Duration::MAX`,
+/// never happens.
+///
+/// # Panics
+///
+/// Panics when `slide` is zero.
+#[must_use]
+pub fn sliding_window<T>(
+    (item_tx, item_rx): (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    window: Duration,
+    slide: Duration,
+) -> (CooldownSender<T>, BatchReceiver<T>)
+where
+    T: 'static + Clone + Debug + Send,
+{
+    assert!(!slide.is_zero(), "slide must be positive");
+    let buffer = Buffer::new(|items| items);
+    let buffered_rx = buffer.shared().subscribe();
+    let sender = CooldownSender::new(item_tx, buffer.shared().clone());
+
+    thread::spawn(move || run(item_rx, buffer, window, slide));
+
+    (sender, BatchReceiver::new(buffered_rx))
+}
+
+/// Alternative to [`cooldown_buffer`](crate::cooldown_buffer) batching items by wall-clock time
+/// instead of activity. Items are emitted at every multiple of `period` since the UNIX epoch,
+/// e.g. at each full minute for `period` of 60 seconds, so the boundaries don't depend on when
+/// the items arrive. Nothing is emitted for periods without items.
+///
+/// [`CooldownSender::flush`] emits items of the current period right away and
+/// [`CooldownSender::set_cooldown`] has no effect. When all the senders are dropped, pending
+/// items are emitted right away.
+///
+/// # Panics
+///
+/// Panics when `period` is zero.
+#[must_use]
+pub fn tumbling_window<T>(
+    (item_tx, item_rx): (impl Into<ItemSender<T>>, Receiver<Message<T>>),
+    period: Duration,
+) -> (CooldownSender<T>, BatchReceiver<T>)
+where
+    T: 'static + Clone + Debug + Send,
+{
+    assert!(!period.is_zero(), "period must be positive");
+    let buffer = Buffer::new(|items| items);
+    let buffered_rx = buffer.shared().subscribe();
+    let sender = CooldownSender::new(item_tx, buffer.shared().clone());
+
+    thread::spawn(move || run_tumbling(item_rx, buffer, period));
+
+    (sender, BatchReceiver::new(buffered_rx))
+}
+
+fn run_tumbling<T>(item_rx: Receiver<Message<T>>, mut buffer: Buffer<T, Vec<T>>, period: Duration)
+where
+    T: Clone,
+{
+    let mut boundary = Instant::now() + until_next_boundary(period);
+    loop {
+        match item_rx.recv_timeout(boundary.saturating_duration_since(Instant::now())) {
+            Ok(Message(Command::Item(item))) => {
+                buffer.push(item);
+                buffer.schedule(Some(boundary));
+            }
+            Ok(Message(Command::Batch(items))) => {
+                buffer.extend(items);
+                buffer.schedule(Some(boundary));
+            }
+            Ok(Message(Command::Requeue(items))) => {
+                buffer.requeue(items);
+                buffer.schedule(Some(boundary));
+            }
+            Ok(Message(Command::Flush)) => buffer.emit(),
+            Ok(Message(Command::Clear)) => buffer.clear(),
+            Ok(Message(Command::Drain(reply))) => {
+                let _ = reply.send(buffer.drain());
+            }
+            Ok(Message(Command::DeadLetters(tx))) => buffer.watch_discarded(tx),
+            Ok(Message(Command::WatchDropped(rx))) => buffer.watch_dropped(rx),
+            Ok(Message(Command::Discard(items))) => buffer.discard(&items),
+            Ok(Message(Command::SetChunkSize(chunk_size))) => buffer.set_chunk_size(chunk_size),
+            Ok(Message(Command::Snapshot(reply))) => {
+                let _ = reply.send(buffer.items().to_vec());
+            }
+            Ok(Message(
+                Command::Ack
+                | Command::Pause
+                | Command::Resume
+                | Command::SetCooldown(_)
+                | Command::SetBurstCooldown(..)
+                | Command::AlignFlushes(_),
+            )) => {}
+            Err(RecvTimeoutError::Timeout) => {
+                buffer.emit();
+                boundary = Instant::now() + until_next_boundary(period);
+            }
+            Ok(Message(Command::ShutdownBy(deadline, reply))) => {
+                buffer.emit_by(deadline, reply);
+                drop(item_rx);
+                buffer.close();
+                return;
+            }
+            Ok(Message(Command::Shutdown)) | Err(RecvTimeoutError::Disconnected) => {
+                buffer.emit();
+                drop(item_rx);
+                buffer.close();
+                return;
+            }
+        }
+    }
+}
+
+/// Returns time left until the next multiple of `period` since the UNIX epoch.
+pub(crate) fn until_next_boundary(period: Duration) -> Duration {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO);
+    let period_nanos = period.as_nanos();
+    let into_period = since_epoch.as_nanos() % period_nanos;
+    let left = u64::try_from(period_nanos - into_period).unwrap_or(u64::MAX);
+    Duration::from_nanos(left)
+}
+
+fn run<T>(
+    item_rx: Receiver<Message<T>>,
+    mut buffer: Buffer<T, Vec<T>>,
+    window: Duration,
+    slide: Duration,
+) where
+    T: Clone,
+{
+    // Arrival times of buffered items, oldest first, the same order as the items.
+    let mut arrivals = VecDeque::new();
+    // Slide too long to be represented never happens.
+    let mut next_slide = Instant::now().checked_add(slide);
+    loop {
+        let received = match next_slide {
+            Some(next_slide) => {
+                item_rx.recv_timeout(next_slide.saturating_duration_since(Instant::now()))
+            }
+            None => item_rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(Message(Command::Item(item))) => {
+                arrivals.push_back(Instant::now());
+                buffer.push(item);
+                buffer.schedule(next_slide);
+            }
+            // Items are kept for the whole window anyway, so requeued ones count as new.
+            Ok(Message(Command::Batch(items) | Command::Requeue(items))) => {
+                arrivals.extend(iter::repeat_n(Instant::now(), items.len()));
+                buffer.extend(items);
+                buffer.schedule(next_slide);
+            }
+            Ok(Message(Command::Flush)) => {
+                forget_older_than(window, &mut arrivals, &mut buffer);
+                buffer.emit_copy();
+            }
+            Ok(Message(Command::Clear)) => {
+                arrivals.clear();
+                buffer.clear();
+            }
+            Ok(Message(Command::Drain(reply))) => {
+                arrivals.clear();
+                let _ = reply.send(buffer.drain());
+            }
+            Ok(Message(Command::DeadLetters(tx))) => buffer.watch_discarded(tx),
+            Ok(Message(Command::WatchDropped(rx))) => buffer.watch_dropped(rx),
+            Ok(Message(Command::Discard(items))) => buffer.discard(&items),
+            Ok(Message(Command::SetChunkSize(chunk_size))) => buffer.set_chunk_size(chunk_size),
+            Ok(Message(Command::Snapshot(reply))) => {
+                let _ = reply.send(buffer.items().to_vec());
+            }
+            Ok(Message(
+                Command::Ack
+                | Command::Pause
+                | Command::Resume
+                | Command::SetCooldown(_)
+                | Command::SetBurstCooldown(..)
+                | Command::AlignFlushes(_),
+            )) => {}
+            Err(RecvTimeoutError::Timeout) => {
+                forget_older_than(window, &mut arrivals, &mut buffer);
+                buffer.emit_copy();
+                next_slide = next_slide.and_then(|next_slide| next_slide.checked_add(slide));
+                // Skip the slides which were missed, e.g. because the thread was not scheduled.
+                let now = Instant::now();
+                if next_slide.is_some_and(|next_slide| next_slide < now) {
+                    next_slide = now.checked_add(slide);
+                }
+                buffer.schedule(next_slide.filter(|_| !arrivals.is_empty()));
+            }
+            Ok(Message(Command::ShutdownBy(deadline, reply))) => {
+                forget_older_than(window, &mut arrivals, &mut buffer);
+                buffer.emit_by(deadline, reply);
+                drop(item_rx);
+                buffer.close();
+                return;
+            }
+            Ok(Message(Command::Shutdown)) | Err(RecvTimeoutError::Disconnected) => {
+                forget_older_than(window, &mut arrivals, &mut buffer);
+                buffer.emit();
+                drop(item_rx);
+                buffer.close();
+                return;
+            }
+        }
+    }
+}
+
+fn forget_older_than<T, B>(
+    window: Duration,
+    arrivals: &mut VecDeque<Instant>,
+    buffer: &mut Buffer<T, B>,
+) {
+    let Some(cutoff) = Instant::now().checked_sub(window) else {
+        return;
+    };
+    let before = arrivals.len();
+    while arrivals.front().is_some_and(|arrival| *arrival < cutoff) {
+        arrivals.pop_front();
+    }
+    buffer.drop_oldest(before - arrivals.len());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Error;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn items_are_emitted_as_long_as_they_are_in_the_window() {
+        // given
+        let (tx, rx) = sliding_window(
+            channel(),
+            Duration::from_millis(150),
+            Duration::from_millis(50),
+        );
+
+        // when
+        tx.send(1).unwrap();
+        let first = rx.recv_timeout(Duration::from_millis(500));
+        tx.send(2).unwrap();
+        let mut batches = Vec::new();
+        while let Ok(batch) = rx.recv_timeout(Duration::from_millis(200)) {
+            batches.push(batch);
+        }
+
+        // then
+        assert_eq!(first, Ok(vec![1]));
+        assert!(batches.contains(&vec![1, 2]));
+        assert_eq!(batches.last(), Some(&vec![2]));
+    }
+
+    #[test]
+    fn huge_slide_never_slides() {
+        // given
+        let (tx, rx) = sliding_window(channel(), Duration::from_secs(60), Duration::MAX);
+
+        // when
+        tx.send(1).unwrap();
+        let sliding = rx.recv_timeout(Duration::from_millis(50));
+        tx.flush().unwrap();
+        let flushed = rx.recv_timeout(Duration::from_millis(500));
+
+        // then
+        assert_eq!(sliding, Err(Error::Timeout));
+        assert_eq!(flushed, Ok(vec![1]));
+        assert_eq!(tx.time_until_flush(), None);
+    }
+
+    #[test]
+    fn tumbling_window_emits_items_at_period_boundaries() {
+        // given
+        let period = Duration::from_millis(100);
+        let (tx, rx) = tumbling_window(channel(), period);
+        thread::sleep(until_next_boundary(period) + Duration::from_millis(10));
+
+        // when
+        tx.send_many(vec![1, 2]).unwrap();
+        let buf = rx.recv_timeout(Duration::from_millis(500));
+        let emitted_at = until_next_boundary(period);
+
+        // then
+        assert_eq!(buf, Ok(vec![1, 2]));
+        // emitted right after the boundary, so almost the whole period is left until the next one
+        assert!(emitted_at > Duration::from_millis(70));
+    }
+
+    #[test]
+    fn boundaries_are_multiples_of_period() {
+        // given
+        let period = Duration::from_secs(60);
+
+        // when
+        let left = until_next_boundary(period);
+        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+
+        // then
+        assert!(left <= period);
+        assert!((since_epoch + left).as_secs() % 60 <= 1);
+    }
+
+    #[test]
+    fn flush_emits_current_window_without_clearing_it() {
+        // given
+        let (tx, rx) = sliding_window(channel(), Duration::from_secs(10), Duration::from_secs(10));
+
+        // when
+        tx.send(1).unwrap();
+        tx.flush().unwrap();
+        tx.send(2).unwrap();
+        tx.flush().unwrap();
+
+        // then
+        assert_eq!(rx.recv_timeout(Duration::from_millis(500)), Ok(vec![1]));
+        assert_eq!(rx.recv_timeout(Duration::from_millis(500)), Ok(vec![1, 2]));
+        assert_eq!(rx.try_recv(), Err(Error::Empty));
+    }
+}
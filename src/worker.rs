@@ -0,0 +1,182 @@
+use crate::buffer::Buffer;
+use crate::sender::{Command, Message};
+use crate::window::until_next_boundary;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Buffer loop. The cooldown is counted with the receive timeout, so the buffer thread is the
+/// only one touching the buffered items.
+pub(crate) fn run<T, B>(
+    item_rx: Receiver<Message<T>>,
+    mut buffer: Buffer<T, B>,
+    cooldown_time: Duration,
+) where
+    T: Clone,
+    B: Clone,
+{
+    let mut cooldown = Cooldown::new(cooldown_time);
+    let mut deadline: Option<Instant> = None;
+    let mut paused = false;
+    loop {
+        let received = match deadline {
+            Some(deadline) => {
+                item_rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => item_rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        // Acknowledgements may come without the message, see `CooldownSender::acker`.
+        buffer.ack();
+        match received {
+            Ok(Message(Command::Item(item))) => {
+                let weight = buffer.weight([&item]);
+                buffer.push(item);
+                deadline = restart(&mut buffer, &cooldown, paused, weight, deadline);
+            }
+            Ok(Message(Command::Batch(items) | Command::Requeue(items))) if items.is_empty() => {}
+            Ok(Message(Command::Batch(items))) => {
+                let weight = buffer.weight(&items);
+                buffer.extend(items);
+                deadline = restart(&mut buffer, &cooldown, paused, weight, deadline);
+            }
+            Ok(Message(Command::Requeue(items))) => {
+                let weight = buffer.weight(&items);
+                buffer.requeue(items);
+                deadline = restart(&mut buffer, &cooldown, paused, weight, deadline);
+            }
+            Ok(Message(Command::Flush)) | Err(RecvTimeoutError::Timeout) => {
+                buffer.emit();
+                deadline = None;
+            }
+            Ok(Message(Command::Clear)) => {
+                buffer.clear();
+                deadline = None;
+            }
+            Ok(Message(Command::Drain(reply))) => {
+                let _ = reply.send(buffer.drain());
+                deadline = None;
+            }
+            Ok(Message(Command::DeadLetters(tx))) => buffer.watch_discarded(tx),
+            Ok(Message(Command::WatchDropped(rx))) => buffer.watch_dropped(rx),
+            Ok(Message(Command::Discard(items))) => buffer.discard(&items),
+            Ok(Message(Command::Ack)) => {}
+            Ok(Message(Command::Pause)) => {
+                paused = true;
+                deadline = None;
+                buffer.schedule(None);
+            }
+            Ok(Message(Command::Resume)) => {
+                paused = false;
+                if !buffer.items().is_empty() {
+                    deadline = restart(&mut buffer, &cooldown, paused, None, None);
+                }
+            }
+            Ok(Message(Command::Snapshot(reply))) => {
+                let _ = reply.send(buffer.items().to_vec());
+            }
+            Ok(Message(Command::SetCooldown(time))) => cooldown.time = time,
+            Ok(Message(Command::SetBurstCooldown(threshold, time))) => {
+                cooldown.burst = Some((threshold, time));
+            }
+            Ok(Message(Command::AlignFlushes(alignment))) => cooldown.alignment = alignment,
+            Ok(Message(Command::SetChunkSize(chunk_size))) => buffer.set_chunk_size(chunk_size),
+            Ok(Message(Command::ShutdownBy(deadline, reply))) => {
+                buffer.ungate();
+                buffer.emit_by(deadline, reply);
+                drop(item_rx);
+                buffer.close();
+                return;
+            }
+            Ok(Message(Command::Shutdown)) | Err(RecvTimeoutError::Disconnected) => {
+                // Nobody may be left to acknowledge the batch, so the last one is not held.
+                buffer.ungate();
+                buffer.emit();
+                // Senders are disconnected first, so they fail once the receivers are closed.
+                drop(item_rx);
+                buffer.close();
+                return;
+            }
+        }
+    }
+}
+
+/// Decides when pending items are emitted.
+struct Cooldown {
+    time: Duration,
+    /// Cooldown used while more than given number of items is pending.
+    burst: Option<(usize, Duration)>,
+    alignment: Option<Duration>,
+}
+
+impl Cooldown {
+    fn new(time: Duration) -> Self {
+        Self {
+            time,
+            burst: None,
+            alignment: None,
+        }
+    }
+
+    /// Returns the cooldown time used while `pending` items are buffered.
+    fn time(&self, pending: usize) -> Duration {
+        match self.burst {
+            Some((threshold, burst)) if pending > threshold => burst,
+            _ => self.time,
+        }
+    }
+
+    /// Returns when the cooldown starting now ends, `None` when it's too long to ever end.
+    /// `pending` items decide whether the burst cooldown is used.
+    ///
+    /// Weighted items extend the cooldown `weight` times, but never make it shorter than the
+    /// `current` one.
+    fn deadline(
+        &self,
+        pending: usize,
+        weight: Option<u32>,
+        current: Option<Instant>,
+    ) -> Option<Instant> {
+        let now = Instant::now();
+        let time = self.time(pending).saturating_mul(weight.unwrap_or(1));
+        let mut deadline = now.checked_add(time);
+        if let (Some(_), Some(current)) = (weight, current) {
+            deadline = deadline.map(|deadline| deadline.max(current));
+        }
+        let boundary = self
+            .alignment
+            .and_then(|period| now.checked_add(until_next_boundary(period)));
+        match (deadline, boundary) {
+            (Some(deadline), Some(boundary)) => Some(deadline.min(boundary)),
+            (deadline, boundary) => deadline.or(boundary),
+        }
+    }
+}
+
+/// Starts the cooldown again and returns when it ends. Without the cooldown, or when the buffer
+/// is full, pending items are emitted right away. While `paused`, only the full buffer is.
+fn restart<T, B>(
+    buffer: &mut Buffer<T, B>,
+    cooldown: &Cooldown,
+    paused: bool,
+    weight: Option<u32>,
+    current: Option<Instant>,
+) -> Option<Instant>
+where
+    T: Clone,
+    B: Clone,
+{
+    if buffer.is_full() {
+        buffer.emit();
+        return None;
+    }
+    if paused {
+        return None;
+    }
+    let pending = buffer.items().len();
+    if cooldown.time(pending).is_zero() {
+        buffer.emit();
+        return None;
+    }
+    let deadline = cooldown.deadline(pending, weight, current);
+    buffer.schedule(deadline);
+    deadline
+}